@@ -1,123 +1,553 @@
-fn parse_input(input: &str) -> Vec<Vec<u8>> {
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+type Height = u16;
+/// Heights by row then column, `None` for impassable terrain.
+type Map = Vec<Vec<Option<Height>>>;
+type Position = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Moves {
+    Four,
+    Eight,
+}
+
+impl Moves {
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Moves::Four => &[(-1, 0), (0, -1), (1, 0), (0, 1)],
+            Moves::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+/// What makes a trail: where it starts and ends, how it may move and what blocks it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TrailRules {
+    start: Height,
+    end: Height,
+    /// Height changes allowed in one step. When they go both ways, e.g. `[-1, 1]`, a trail could
+    /// come back to a cell, so trails never revisit one and are counted by walking each of them
+    /// rather than by the DP over heights.
+    deltas: Vec<i32>,
+    moves: Moves,
+    impassable: Vec<char>,
+}
+
+impl Default for TrailRules {
+    /// The puzzle's rules, climbing one at a time from 0 to 9 without diagonals
+    fn default() -> Self {
+        Self {
+            start: 0,
+            end: 9,
+            deltas: vec![1],
+            moves: Moves::Four,
+            impassable: vec!['.'],
+        }
+    }
+}
+
+impl TrailRules {
+    fn new(
+        start: Height,
+        end: Height,
+        deltas: Vec<i32>,
+        moves: Moves,
+        impassable: Vec<char>,
+    ) -> Result<Self, String> {
+        if deltas.is_empty() || deltas.contains(&0) {
+            return Err("deltas must be non-empty and non-zero".to_string());
+        }
+        if start != end && !deltas.iter().any(|delta| (*delta > 0) == (end > start)) {
+            return Err(format!(
+                "can't get from {start} to {end} with deltas {deltas:?}"
+            ));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            deltas,
+            moves,
+            impassable,
+        })
+    }
+
+    fn climbing(&self) -> bool {
+        self.deltas[0] > 0
+    }
+
+    /// Every delta goes the same way, so heights only ever move towards the end.
+    fn monotonic(&self) -> bool {
+        self.deltas
+            .iter()
+            .all(|delta| (*delta > 0) == self.climbing())
+    }
+
+    /// Whether a trail could pass through `height` on its way from start to end.
+    fn on_the_way(&self, height: Height) -> bool {
+        (self.start.min(self.end)..=self.start.max(self.end)).contains(&height)
+    }
+
+    /// Positions one allowed step on from `(x, y)`. Trails stop once they reach the end height.
+    fn steps(&self, map: &Map, (x, y): Position) -> Vec<Position> {
+        let Some(height) = map[y][x] else {
+            return Vec::new();
+        };
+        if height == self.end {
+            return Vec::new();
+        }
+
+        self.moves
+            .offsets()
+            .iter()
+            .filter_map(|diff| {
+                let new_x = x.checked_add_signed(diff.0)?;
+                let new_y = y.checked_add_signed(diff.1)?;
+                let next = (*map.get(new_y)?.get(new_x)?)?;
+                (self.on_the_way(next) && self.deltas.contains(&(next as i32 - height as i32)))
+                    .then_some((new_x, new_y))
+            })
+            .collect()
+    }
+}
+
+/// How heights are written in a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// One character per cell, `0` to `9`
+    Decimal,
+    /// One character per cell, `0` to `F` in either case
+    Hex,
+    /// Cells separated by whitespace, heights of any width up to `u16::MAX`
+    Separated,
+}
+
+impl Format {
+    /// Whitespace inside a line means separated cells, otherwise any hex letter that isn't an
+    /// impassable marker means a hex map.
+    fn detect(input: &str, rules: &TrailRules) -> Self {
+        if input
+            .lines()
+            .any(|line| line.trim().contains(char::is_whitespace))
+        {
+            Format::Separated
+        } else if input.chars().any(|chr| {
+            chr.is_ascii_hexdigit() && !chr.is_ascii_digit() && !rules.impassable.contains(&chr)
+        }) {
+            Format::Hex
+        } else {
+            Format::Decimal
+        }
+    }
+}
+
+fn parse_input(input: &str, rules: &TrailRules) -> Result<Map, String> {
+    let format = Format::detect(input, rules);
     let mut map = Vec::new();
-    for line in input.lines() {
+    for (y, line) in input.lines().enumerate() {
+        let cells: Vec<&str> = if format == Format::Separated {
+            line.split_whitespace().collect()
+        } else {
+            line.char_indices()
+                .map(|(i, chr)| &line[i..i + chr.len_utf8()])
+                .collect()
+        };
+
         let mut row = Vec::new();
-        for chr in line.chars() {
-            if chr == '.' {
-                row.push(255);
+        for (x, cell) in cells.into_iter().enumerate() {
+            let mut chrs = cell.chars();
+            let marker = chrs.next().filter(|_| chrs.next().is_none());
+            let height = match format {
+                Format::Decimal => cell.parse().ok(),
+                Format::Hex => Height::from_str_radix(cell, 16).ok(),
+                Format::Separated => cell.parse().ok(),
+            };
+
+            if marker.is_some_and(|chr| rules.impassable.contains(&chr)) {
+                row.push(None);
+            } else if let Some(height) = height {
+                row.push(Some(height));
             } else {
-                row.push(chr.to_digit(10).unwrap() as u8);
+                return Err(format!(
+                    "unexpected {cell:?} at line {}, cell {}",
+                    y + 1,
+                    x + 1
+                ));
             }
         }
         map.push(row);
     }
-    map
-}
-
-mod part1 {
-    use std::collections::HashSet;
-
-    fn follow_trail(
-        x: usize,
-        y: usize,
-        completed_trails: &mut u64,
-        visited: &mut HashSet<(usize, usize)>,
-        map: &[Vec<u8>],
-    ) {
-        let cur_height = map[y][x];
-
-        if cur_height == 9 {
-            *completed_trails += 1;
-            return;
-        }
-
-        let next_height = cur_height + 1;
-        for diff in &[(-1, 0), (0, -1), (1, 0), (0, 1)] {
-            if let (Some(new_x), Some(new_y)) =
-                (x.checked_add_signed(diff.0), y.checked_add_signed(diff.1))
-            {
-                if new_x < map[0].len()
-                    && new_y < map.len()
-                    && map[new_y][new_x] == next_height
-                    && !visited.contains(&(new_x, new_y))
-                {
-                    visited.insert((new_x, new_y));
-                    follow_trail(new_x, new_y, completed_trails, visited, map);
-                }
+    Ok(map)
+}
+
+/// Work through the map one height at a time from the end height back to the start, so every
+/// cell's value is built from the finished values of the cells it can step to. `summit` gives a
+/// value for a cell at the end height and `combine` folds a next step's value into a cell's.
+/// Returns the value of every trailhead with at least one trail. Only for monotonic rules.
+fn trail_dp<T: Clone>(
+    map: &Map,
+    rules: &TrailRules,
+    summit: impl Fn(Position) -> T,
+    combine: impl Fn(&mut T, &T),
+) -> Vec<(Position, T)> {
+    let mut layers: BTreeMap<Height, Vec<Position>> = BTreeMap::new();
+    for (y, row) in map.iter().enumerate() {
+        for (x, height) in row.iter().enumerate() {
+            if let Some(height) = height.filter(|h| rules.on_the_way(*h)) {
+                layers.entry(height).or_default().push((x, y));
             }
         }
     }
+    let layers: Vec<_> = if rules.climbing() {
+        layers.into_iter().rev().collect()
+    } else {
+        layers.into_iter().collect()
+    };
+    let max_delta = rules.deltas.iter().map(|d| d.unsigned_abs()).max().unwrap();
 
-    pub fn solve1(input: &[Vec<u8>]) -> u64 {
-        let mut completed_trails = 0;
+    let mut values: HashMap<Position, T> = HashMap::new();
+    let mut trailheads = Vec::new();
+    let mut freed = 0;
+    for (height, cells) in &layers {
+        for pos in cells {
+            let value = if *height == rules.end {
+                Some(summit(*pos))
+            } else {
+                let mut value: Option<T> = None;
+                for next in rules.steps(map, *pos) {
+                    match (&mut value, values.get(&next)) {
+                        (Some(value), Some(next)) => combine(value, next),
+                        (None, Some(next)) => value = Some(next.clone()),
+                        (_, None) => {}
+                    }
+                }
+                value
+            };
 
-        for (y, row) in input.iter().enumerate() {
-            for (x, col) in row.iter().enumerate() {
-                if *col == 0 {
-                    let mut visited = HashSet::new();
-                    follow_trail(x, y, &mut completed_trails, &mut visited, input);
+            if let Some(value) = value {
+                if *height == rules.start {
+                    trailheads.push((*pos, value.clone()));
                 }
+                values.insert(*pos, value);
+            }
+        }
+
+        // Nothing still to come can step `max_delta` or more heights back, so free those layers
+        while layers[freed].0.abs_diff(*height) as u32 >= max_delta {
+            for pos in &layers[freed].1 {
+                values.remove(pos);
             }
+            freed += 1;
         }
-        completed_trails
     }
+
+    trailheads
 }
 
-mod part2 {
-    use std::collections::HashMap;
+/// Distinct end cells reachable from each trailhead with at least one.
+fn reachable_summits(map: &Map, rules: &TrailRules) -> HashMap<Position, BTreeSet<Position>> {
+    if !rules.monotonic() {
+        return trails::trailheads(map, rules)
+            .into_iter()
+            .filter_map(|start| {
+                let summits = trails::reachable(map, rules, start)
+                    .into_iter()
+                    .filter(|(x, y)| map[*y][*x] == Some(rules.end))
+                    .collect::<BTreeSet<_>>();
+                (!summits.is_empty()).then_some((start, summits))
+            })
+            .collect();
+    }
+
+    trail_dp(
+        map,
+        rules,
+        |pos| BTreeSet::from([pos]),
+        |summits, next| summits.extend(next),
+    )
+    .into_iter()
+    .collect()
+}
+
+fn solve1(map: &Map, rules: &TrailRules) -> u64 {
+    reachable_summits(map, rules)
+        .values()
+        .map(|summits| summits.len() as u64)
+        .sum()
+}
+
+fn solve2(map: &Map, rules: &TrailRules) -> u64 {
+    if !rules.monotonic() {
+        return trails::trailheads(map, rules)
+            .into_iter()
+            .map(|start| trails::Trails::new(map, rules, start).count() as u64)
+            .sum();
+    }
+
+    trail_dp(map, rules, |_| 1, |paths, next| *paths += next)
+        .into_iter()
+        .map(|(_, paths)| paths)
+        .sum()
+}
+
+mod trails {
+    use super::*;
+
+    pub fn trailheads(map: &Map, rules: &TrailRules) -> Vec<Position> {
+        let mut heads = Vec::new();
+        for (y, row) in map.iter().enumerate() {
+            for (x, height) in row.iter().enumerate() {
+                if *height == Some(rules.start) {
+                    heads.push((x, y));
+                }
+            }
+        }
+        heads
+    }
+
+    /// Every cell some sequence of steps leads to from `start`, including `start` itself.
+    pub fn reachable(map: &Map, rules: &TrailRules, start: Position) -> HashSet<Position> {
+        let mut seen = HashSet::from([start]);
+        let mut stack = vec![start];
+        while let Some(pos) = stack.pop() {
+            for next in rules.steps(map, pos) {
+                if seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        seen
+    }
 
-    fn follow_trail(
-        x: usize,
-        y: usize,
-        visited: &mut HashMap<(usize, usize), u64>,
-        map: &[Vec<u8>],
-    ) -> u64 {
-        let cur_height = map[y][x];
+    /// Every distinct trail from a trailhead to the end height, depth first so trails are
+    /// produced one at a time rather than all held in memory. Trails never go back over a cell
+    /// they've already visited.
+    pub struct Trails<'a> {
+        map: &'a Map,
+        rules: &'a TrailRules,
+        /// The trail so far, and the untried steps from each position on it
+        path: Vec<Position>,
+        pending: Vec<Vec<Position>>,
+    }
 
-        if cur_height == 9 {
-            return 1;
+    impl<'a> Trails<'a> {
+        pub fn new(map: &'a Map, rules: &'a TrailRules, start: Position) -> Self {
+            Self {
+                map,
+                rules,
+                path: vec![start],
+                pending: vec![rules.steps(map, start)],
+            }
         }
+    }
 
-        let next_height = cur_height + 1;
-        let mut tot_paths = 0;
-        for diff in &[(-1, 0), (0, -1), (1, 0), (0, 1)] {
-            if let (Some(new_x), Some(new_y)) =
-                (x.checked_add_signed(diff.0), y.checked_add_signed(diff.1))
-            {
-                if new_x < map[0].len() && new_y < map.len() && map[new_y][new_x] == next_height {
-                    if let Some(answer) = visited.get(&(new_x, new_y)) {
-                        tot_paths += *answer;
-                    } else {
-                        tot_paths += follow_trail(new_x, new_y, visited, map);
+    impl Iterator for Trails<'_> {
+        type Item = Vec<Position>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            loop {
+                let &(x, y) = self.path.last()?;
+                if self.map[y][x] == Some(self.rules.end) {
+                    let trail = self.path.clone();
+                    self.path.pop();
+                    self.pending.pop();
+                    return Some(trail);
+                }
+
+                match self.pending.last_mut()?.pop() {
+                    Some(next) if self.path.contains(&next) => {}
+                    Some(next) => {
+                        self.path.push(next);
+                        self.pending.push(self.rules.steps(self.map, next));
+                    }
+                    None => {
+                        self.path.pop();
+                        self.pending.pop();
                     }
                 }
             }
         }
+    }
+
+    #[derive(Debug)]
+    pub struct TrailheadReport {
+        pub start: Position,
+        pub summits: BTreeSet<Position>,
+        /// At most `cap` of the trails from `start`
+        pub trails: Vec<Vec<Position>>,
+        /// Whether there were more than `cap` trails
+        pub truncated: bool,
+    }
+
+    /// Summits and trails for every trailhead, keeping no more than `cap` trails for each.
+    pub fn trailhead_reports(map: &Map, rules: &TrailRules, cap: usize) -> Vec<TrailheadReport> {
+        let mut summits = reachable_summits(map, rules);
+        trailheads(map, rules)
+            .into_iter()
+            .map(|start| {
+                let mut all = Trails::new(map, rules, start);
+                let trails: Vec<_> = all.by_ref().take(cap).collect();
+                TrailheadReport {
+                    start,
+                    summits: summits.remove(&start).unwrap_or_default(),
+                    trails,
+                    truncated: all.next().is_some(),
+                }
+            })
+            .collect()
+    }
+}
+
+mod render {
+    use super::{Map, Position};
 
-        assert!(visited.insert((x, y), tot_paths).is_none());
-        tot_paths
+    const HIGHLIGHT: &str = "\x1b[1;32m";
+    const RESET: &str = "\x1b[0m";
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Style {
+        Plain,
+        Ansi,
     }
 
-    pub fn solve2(input: &[Vec<u8>]) -> u64 {
-        let mut total_score = 0;
+    /// Draw the map with `trail` picked out. With `Style::Plain` every height off the trail is
+    /// replaced by `.`, as in the puzzle text; with `Style::Ansi` the whole map is drawn and the
+    /// trail coloured. Heights up to 15 are drawn as one hex digit, any higher and every cell is
+    /// right aligned to the widest height and separated by spaces.
+    pub fn render(map: &Map, trail: &[Position], style: Style) -> String {
+        let highest = map.iter().flatten().flatten().max().copied().unwrap_or(0);
+        let (width, separator) = if highest < 16 {
+            (1, "")
+        } else {
+            (highest.to_string().len(), " ")
+        };
+
+        let mut output = String::new();
+        for (y, row) in map.iter().enumerate() {
+            for (x, height) in row.iter().enumerate() {
+                if x > 0 {
+                    output.push_str(separator);
+                }
+                let symbol = match height {
+                    None => ".".to_string(),
+                    Some(h) if width == 1 => format!("{h:X}"),
+                    Some(h) => h.to_string(),
+                };
+                let on_trail = trail.contains(&(x, y));
+                let symbol = if on_trail || style == Style::Ansi {
+                    symbol
+                } else {
+                    ".".to_string()
+                };
 
-        for (y, row) in input.iter().enumerate() {
-            for (x, col) in row.iter().enumerate() {
-                if *col == 0 {
-                    let mut visited = HashMap::new();
-                    total_score += follow_trail(x, y, &mut visited, input);
+                output.push_str(&" ".repeat(width - symbol.len()));
+                if on_trail && style == Style::Ansi {
+                    output.push_str(HIGHLIGHT);
+                    output.push_str(&symbol);
+                    output.push_str(RESET);
+                } else {
+                    output.push_str(&symbol);
                 }
             }
+            output.push('\n');
         }
-        total_score
+        output
     }
 }
 
+/// `--start <height> --end <height> --deltas <d,d,..> --diagonal --impassable <chars>`, each
+/// defaulting to the puzzle's rules.
+fn rules_from_args(args: &[String]) -> TrailRules {
+    let value = |flag: &str| {
+        let idx = args.iter().position(|arg| arg == flag)?;
+        Some(
+            args.get(idx + 1)
+                .unwrap_or_else(|| panic!("Missing value for {flag}")),
+        )
+    };
+    let height = |flag: &str, default: Height| {
+        value(flag).map_or(default, |h| h.parse().expect("Invalid height"))
+    };
+
+    let default = TrailRules::default();
+    let deltas = value("--deltas").map_or(default.deltas, |deltas| {
+        deltas
+            .split(',')
+            .map(|d| d.trim().parse().expect("Invalid delta"))
+            .collect()
+    });
+    let moves = if args.iter().any(|arg| arg == "--diagonal") {
+        Moves::Eight
+    } else {
+        Moves::Four
+    };
+    let impassable =
+        value("--impassable").map_or(default.impassable, |chrs| chrs.chars().collect());
+
+    TrailRules::new(
+        height("--start", default.start),
+        height("--end", default.end),
+        deltas,
+        moves,
+        impassable,
+    )
+    .unwrap_or_else(|e| panic!("{e}"))
+}
+
 fn main() {
-    let parsed = parse_input(include_str!("input.txt"));
+    let args: Vec<String> = std::env::args().collect();
+    let rules = rules_from_args(&args);
+    let parsed = parse_input(include_str!("input.txt"), &rules).unwrap_or_else(|e| panic!("{e}"));
 
-    println!("Part 1: {}", part1::solve1(&parsed));
-    println!("Part 2: {}", part2::solve2(&parsed));
+    println!("Part 1: {}", solve1(&parsed, &rules));
+    println!("Part 2: {}", solve2(&parsed, &rules));
+
+    // `--trails <cap>` lists every trailhead's summits and up to `cap` of its trails
+    if let Some(idx) = args.iter().position(|arg| arg == "--trails") {
+        let cap = args
+            .get(idx + 1)
+            .map_or(10, |cap| cap.parse().expect("Invalid trail cap"));
+        for report in trails::trailhead_reports(&parsed, &rules, cap) {
+            println!(
+                "Trailhead {:?}: {} summits, {}{} trails",
+                report.start,
+                report.summits.len(),
+                report.trails.len(),
+                if report.truncated { "+" } else { "" }
+            );
+            for trail in &report.trails {
+                println!("  {trail:?}");
+            }
+        }
+    }
+
+    // `--render <trailhead> <trail> [--plain]` draws one trail, both counted from 0
+    if let Some(idx) = args.iter().position(|arg| arg == "--render") {
+        let index = |offset: usize| -> usize {
+            args.get(idx + offset)
+                .and_then(|arg| arg.parse().ok())
+                .expect("Missing trailhead or trail index")
+        };
+        let start = trails::trailheads(&parsed, &rules)[index(1)];
+        let trail = trails::Trails::new(&parsed, &rules, start)
+            .nth(index(2))
+            .expect("No such trail");
+        let style = if args.iter().any(|arg| arg == "--plain") {
+            render::Style::Plain
+        } else {
+            render::Style::Ansi
+        };
+        print!("{}", render::render(&parsed, &trail, style));
+    }
 }
 
 #[cfg(test)]
@@ -134,42 +564,294 @@ mod tests {
 
     #[test]
     fn parse_test() {
-        let parsed = parse_input(INPUT1);
-        assert_eq!(parsed[0][0], 0);
-        assert_eq!(parsed[3][2], 7);
+        let parsed = parse_input(INPUT1, &TrailRules::default()).unwrap();
+        assert_eq!(parsed[0][0], Some(0));
+        assert_eq!(parsed[3][2], Some(7));
 
-        let parsed = parse_input(INPUT2);
-        assert_eq!(parsed[0][0], 255);
-        assert_eq!(parsed[0][3], 0);
+        let parsed = parse_input(INPUT2, &TrailRules::default()).unwrap();
+        assert_eq!(parsed[0][0], None);
+        assert_eq!(parsed[0][3], Some(0));
     }
 
     #[test]
     fn test1() {
-        let parsed = parse_input(INPUT1);
-        assert_eq!(part1::solve1(&parsed), 1);
+        let parsed = parse_input(INPUT1, &TrailRules::default()).unwrap();
+        assert_eq!(solve1(&parsed, &TrailRules::default()), 1);
 
-        let parsed = parse_input(INPUT2);
-        assert_eq!(part1::solve1(&parsed), 2);
+        let parsed = parse_input(INPUT2, &TrailRules::default()).unwrap();
+        assert_eq!(solve1(&parsed, &TrailRules::default()), 2);
 
-        let parsed = parse_input(INPUT4);
-        assert_eq!(part1::solve1(&parsed), 4);
+        let parsed = parse_input(INPUT4, &TrailRules::default()).unwrap();
+        assert_eq!(solve1(&parsed, &TrailRules::default()), 4);
 
-        let parsed = parse_input(INPUT3);
-        assert_eq!(part1::solve1(&parsed), 36);
+        let parsed = parse_input(INPUT3, &TrailRules::default()).unwrap();
+        assert_eq!(solve1(&parsed, &TrailRules::default()), 36);
     }
 
     #[test]
     fn test2() {
-        let parsed = parse_input(INPUT5);
-        assert_eq!(part2::solve2(&parsed), 3);
+        let parsed = parse_input(INPUT5, &TrailRules::default()).unwrap();
+        assert_eq!(solve2(&parsed, &TrailRules::default()), 3);
+
+        let parsed = parse_input(INPUT6, &TrailRules::default()).unwrap();
+        assert_eq!(solve2(&parsed, &TrailRules::default()), 13);
+
+        let parsed = parse_input(INPUT7, &TrailRules::default()).unwrap();
+        assert_eq!(solve2(&parsed, &TrailRules::default()), 227);
+
+        let parsed = parse_input(INPUT3, &TrailRules::default()).unwrap();
+        assert_eq!(solve2(&parsed, &TrailRules::default()), 81);
+    }
+
+    #[test]
+    fn test_trails() {
+        let rules = TrailRules::default();
+        let parsed = parse_input(INPUT1, &rules).unwrap();
+        let trails: Vec<_> = trails::Trails::new(&parsed, &rules, (0, 0)).collect();
+        assert_eq!(trails.len(), 16);
+        for trail in &trails {
+            assert_eq!(trail.len(), 10);
+            for (height, (x, y)) in trail.iter().enumerate() {
+                assert_eq!(parsed[*y][*x], Some(height as Height));
+            }
+        }
+        let distinct: std::collections::HashSet<_> = trails.iter().collect();
+        assert_eq!(distinct.len(), 16);
+
+        let parsed = parse_input(INPUT3, &rules).unwrap();
+        let reports = trails::trailhead_reports(&parsed, &rules, usize::MAX);
+        let scores: usize = reports.iter().map(|r| r.summits.len()).sum();
+        let ratings: usize = reports.iter().map(|r| r.trails.len()).sum();
+        assert_eq!((scores, ratings), (36, 81));
+        assert!(reports.iter().all(|r| !r.truncated));
+        assert_eq!(reports[0].start, (2, 0));
+        assert_eq!(
+            reports[0].summits.iter().copied().collect::<Vec<_>>(),
+            [(0, 3), (1, 0), (4, 3), (4, 5), (5, 4)]
+        );
+
+        // Walking down instead starts from the 9s and ends at the 0s
+        let rules = TrailRules::new(9, 0, vec![-1], Moves::Four, vec!['.']).unwrap();
+        let parsed = parse_input(INPUT1, &rules).unwrap();
+        let trails: Vec<_> = trails::Trails::new(&parsed, &rules, (0, 3)).collect();
+        assert_eq!(trails.len(), 16);
+        for trail in &trails {
+            for (depth, (x, y)) in trail.iter().enumerate() {
+                assert_eq!(parsed[*y][*x], Some(9 - depth as Height));
+            }
+        }
+        assert_eq!(trails::trailheads(&parsed, &rules), [(0, 3)]);
+
+        let parsed = parse_input(INPUT3, &rules).unwrap();
+        let reports = trails::trailhead_reports(&parsed, &rules, usize::MAX);
+        assert_eq!(reports.len(), 7);
+        assert_eq!(reports[0].start, (1, 0));
+        assert_eq!(
+            reports[0].summits.iter().copied().collect::<Vec<_>>(),
+            [(0, 6), (1, 7), (2, 0), (4, 0), (4, 2)]
+        );
+        let scores: usize = reports.iter().map(|r| r.summits.len()).sum();
+        let ratings: usize = reports.iter().map(|r| r.trails.len()).sum();
+        assert_eq!((scores, ratings), (36, 81));
+    }
+
+    #[test]
+    fn test_trails_cap() {
+        let rules = TrailRules::default();
+        let parsed = parse_input(INPUT7, &rules).unwrap();
+        let reports = trails::trailhead_reports(&parsed, &rules, 10);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].trails.len(), 10);
+        assert!(reports[0].truncated);
+        assert_eq!(
+            trails::Trails::new(&parsed, &rules, reports[0].start).count(),
+            227
+        );
+
+        // Diagonal steps open up more trails, which the DP and the iterator agree on
+        let rules = TrailRules {
+            moves: Moves::Eight,
+            ..TrailRules::default()
+        };
+        let parsed = parse_input(INPUT3, &rules).unwrap();
+        let reports = trails::trailhead_reports(&parsed, &rules, 10);
+        let capped: Vec<_> = reports
+            .iter()
+            .map(|r| (r.trails.len(), r.truncated))
+            .collect();
+        assert_eq!(
+            capped,
+            [
+                (10, true),
+                (10, true),
+                (10, false),
+                (4, false),
+                (9, false),
+                (4, false),
+                (7, false),
+                (8, false),
+                (7, false)
+            ]
+        );
+        let count: usize = trails::trailheads(&parsed, &rules)
+            .into_iter()
+            .map(|start| trails::Trails::new(&parsed, &rules, start).count())
+            .sum();
+        assert_eq!(count, 93);
+        assert_eq!(solve2(&parsed, &rules), 93);
+    }
+
+    #[test]
+    fn test_render() {
+        let rules = TrailRules::default();
+        let parsed = parse_input(INPUT5, &rules).unwrap();
+        let trail = trails::Trails::new(&parsed, &rules, (5, 0)).next().unwrap();
+        assert_eq!(
+            render::render(&parsed, &trail, render::Style::Plain),
+            ".....0.\n.....1.\n.....2.\n.....3.\n.....4.\n..8765.\n..9....\n"
+        );
+
+        let ansi = render::render(&parsed, &trail, render::Style::Ansi);
+        assert!(ansi.starts_with(".....\x1b[1;32m0\x1b[0m.\n..432\x1b[1;32m1\x1b[0m.\n"));
+        assert!(ansi.ends_with("..\x1b[1;32m9\x1b[0m....\n"));
+
+        // The same map walked down from the 9
+        let rules = TrailRules::new(9, 0, vec![-1], Moves::Four, vec!['.']).unwrap();
+        let trails: Vec<_> = trails::Trails::new(&parsed, &rules, (2, 6)).collect();
+        assert_eq!(trails.len(), 3);
+        assert!(trails
+            .iter()
+            .all(|trail| trail[0] == (2, 6) && trail[9] == (5, 0)));
+        assert_eq!(
+            render::render(&parsed, &trails[2], render::Style::Plain),
+            ".....0.\n..4321.\n..5....\n..6....\n..7....\n..8....\n..9....\n"
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        // Descending from 9 to 0 finds the same trails the other way round
+        let descending = TrailRules::new(9, 0, vec![-1], Moves::Four, vec!['.']).unwrap();
+        let parsed = parse_input(INPUT3, &descending).unwrap();
+        assert_eq!(solve2(&parsed, &descending), 81);
+        assert_eq!(solve1(&parsed, &descending), 36);
+
+        // 0-1-3 and 0-2-3, only possible with climbs of 2
+        let big_steps = TrailRules::new(0, 3, vec![1, 2], Moves::Four, vec![]).unwrap();
+        let parsed = parse_input("01\n23", &big_steps).unwrap();
+        assert_eq!(solve2(&parsed, &big_steps), 2);
+        assert_eq!(solve1(&parsed, &big_steps), 1);
+        assert_eq!(trails::Trails::new(&parsed, &big_steps, (0, 0)).count(), 2);
+        let small_steps = TrailRules {
+            deltas: vec![1],
+            ..big_steps.clone()
+        };
+        assert_eq!(solve2(&parsed, &small_steps), 0);
+
+        let diagonal = TrailRules::new(0, 2, vec![1], Moves::Eight, vec!['#']).unwrap();
+        let parsed = parse_input("0##\n#1#\n##2", &diagonal).unwrap();
+        assert_eq!(parsed[0][1], None);
+        assert_eq!(solve2(&parsed, &diagonal), 1);
+        let straight = TrailRules {
+            moves: Moves::Four,
+            ..diagonal.clone()
+        };
+        assert_eq!(solve2(&parsed, &straight), 0);
+        assert!(parse_input("0##", &TrailRules::default()).is_err());
+
+        // Starting at the end is a trail of one cell
+        let flat = TrailRules::new(5, 5, vec![1], Moves::Four, vec![]).unwrap();
+        let parsed = parse_input("565", &flat).unwrap();
+        assert_eq!((solve1(&parsed, &flat), solve2(&parsed, &flat)), (2, 2));
+
+        assert!(TrailRules::new(0, 9, vec![], Moves::Four, vec![]).is_err());
+        assert!(TrailRules::new(0, 9, vec![0], Moves::Four, vec![]).is_err());
+        assert!(TrailRules::new(0, 9, vec![-1, -2], Moves::Four, vec![]).is_err());
+        assert!(TrailRules::new(0, 9, vec![-1], Moves::Four, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_rules_both_ways() {
+        // Going back down lets each 0 get round the other 0 to the 2, but never revisit a cell
+        let both_ways = TrailRules::new(0, 2, vec![-1, 1], Moves::Four, vec!['.']).unwrap();
+        let parsed = parse_input("012\n10.", &both_ways).unwrap();
+        assert_eq!(solve1(&parsed, &both_ways), 2);
+        assert_eq!(solve2(&parsed, &both_ways), 4);
+        assert_eq!(
+            trails::Trails::new(&parsed, &both_ways, (0, 0)).collect::<Vec<_>>(),
+            [
+                vec![(0, 0), (0, 1), (1, 1), (1, 0), (2, 0)],
+                vec![(0, 0), (1, 0), (2, 0)],
+            ]
+        );
+
+        let climbing = TrailRules {
+            deltas: vec![1],
+            ..both_ways.clone()
+        };
+        assert_eq!(solve1(&parsed, &climbing), 2);
+        assert_eq!(solve2(&parsed, &climbing), 2);
+    }
+
+    #[test]
+    fn test_formats() {
+        let rules = TrailRules::default();
+        let decimal = parse_input(INPUT3, &rules).unwrap();
+        assert_eq!(Format::detect(INPUT3, &rules), Format::Decimal);
+
+        // The same map written every other way parses the same
+        let separated: String = INPUT3
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    + "\n"
+            })
+            .collect();
+        assert_eq!(Format::detect(&separated, &rules), Format::Separated);
+        assert_eq!(parse_input(&separated, &rules).unwrap(), decimal);
 
-        let parsed = parse_input(INPUT6);
-        assert_eq!(part2::solve2(&parsed), 13);
+        // Climbing 0 to F through hex digits, with `.` still impassable
+        let hex_rules = TrailRules::new(0, 15, vec![1], Moves::Four, vec!['.']).unwrap();
+        let input = "0123.\n7654.\n89ab.\nfedc.";
+        assert_eq!(Format::detect(input, &hex_rules), Format::Hex);
+        let parsed = parse_input(input, &hex_rules).unwrap();
+        assert_eq!(parsed[2][2], Some(10));
+        assert_eq!(parsed[3][0], Some(15));
+        assert_eq!(parsed[0][4], None);
+        assert_eq!(
+            parse_input(&input.to_uppercase(), &hex_rules).unwrap(),
+            parsed
+        );
+        assert_eq!(solve2(&parsed, &hex_rules), 1);
+        let trail = trails::Trails::new(&parsed, &hex_rules, (0, 0))
+            .next()
+            .unwrap();
+        assert_eq!(
+            render::render(&parsed, &trail, render::Style::Plain),
+            "0123.\n7654.\n89AB.\nFEDC.\n"
+        );
 
-        let parsed = parse_input(INPUT7);
-        assert_eq!(part2::solve2(&parsed), 227);
+        // Heights in the hundreds and thousands, climbing in steps of 100 or 200
+        let big_rules = TrailRules::new(100, 1000, vec![100, 200], Moves::Four, vec!['#']).unwrap();
+        let input = "100 300 500 # 1000\n200 # 600 700 900\n  400 # 800 # 65535\n";
+        assert_eq!(Format::detect(input, &big_rules), Format::Separated);
+        let parsed = parse_input(input, &big_rules).unwrap();
+        assert_eq!(parsed[2][4], Some(u16::MAX));
+        assert_eq!(solve1(&parsed, &big_rules), 1);
+        assert_eq!(solve2(&parsed, &big_rules), 1);
+        let trail = trails::Trails::new(&parsed, &big_rules, (0, 0))
+            .next()
+            .unwrap();
+        assert_eq!(
+            render::render(&parsed, &trail, render::Style::Plain),
+            "  100   300   500     .  1000\n    .     .   600   700   900\n    .     .     .     .     .\n"
+        );
 
-        let parsed = parse_input(INPUT3);
-        assert_eq!(part2::solve2(&parsed), 81);
+        assert!(parse_input("1 2 65536", &rules).is_err());
+        assert!(parse_input("1 2 x", &rules).is_err());
+        assert!(parse_input("12g4", &rules).is_err());
     }
 }
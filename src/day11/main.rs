@@ -1,124 +1,293 @@
-fn parse_input(input: &str) -> Vec<String> {
+use std::collections::HashMap;
+
+use rules::RuleSet;
+
+/// How many stones are engraved with each value, since order never matters.
+type Stones = HashMap<u64, u128>;
+
+fn parse_input(input: &str) -> Vec<u64> {
     input
         .split_ascii_whitespace()
-        .map(|s| s.to_string())
+        .map(|s| s.parse().unwrap())
         .collect()
 }
 
-mod part1 {
-    fn update_stones(stones: Vec<String>) -> Vec<String> {
-        let mut new_stones = Vec::new();
-        for stone in stones {
-            if stone == "0" {
-                new_stones.push(String::from("1"));
-            } else if stone.len() % 2 == 0 {
-                let (left, right) = stone.split_at(stone.len() / 2);
-                let mut left = left.trim_start_matches("0");
-                if left.is_empty() {
-                    left = "0";
-                }
-                let mut right = right.trim_start_matches("0");
-                if right.is_empty() {
-                    right = "0";
-                }
-                new_stones.push(left.to_string());
-                new_stones.push(right.to_string());
-            } else {
-                let val: u64 = stone.parse().unwrap();
-                new_stones.push((val * 2024).to_string());
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    /// More than `u128::MAX` stones
+    Count,
+    /// A stone's new value doesn't fit in a `u64`
+    Value(u64),
+}
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Overflow::Count => write!(f, "more than {} stones", u128::MAX),
+            Overflow::Value(stone) => write!(f, "stone {stone} grew past {}", u64::MAX),
         }
+    }
+}
 
-        new_stones
+/// Split a number with an even count of digits into its left and right halves.
+fn split_digits(stone: u64) -> Option<(u64, u64)> {
+    let digits = stone.checked_ilog10()? + 1;
+    if digits % 2 != 0 {
+        return None;
     }
+    let half = 10u64.pow(digits / 2);
+    Some((stone / half, stone % half))
+}
 
-    pub fn solve1(input: &[String]) -> usize {
-        let mut stones = input.to_owned();
-        for _ in 0..25 {
-            stones = update_stones(stones);
-        }
+mod rules {
+    use super::*;
 
-        stones.len()
+    use nom::branch::alt;
+    use nom::bytes::complete::tag;
+    use nom::character::complete::{space0, u64 as parse_u64};
+    use nom::combinator::{all_consuming, map, value};
+    use nom::sequence::{delimited, preceded, separated_pair};
+    use nom::IResult;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Predicate {
+        Always,
+        Equals(u64),
+        EvenDigits,
+        OddDigits,
+        DivisibleBy(u64),
     }
-}
 
-mod part2 {
-    use std::collections::HashMap;
-
-    fn update_stone(
-        stone: u64,
-        max_depth: u64,
-        cur_depth: u64,
-        lookup: &mut HashMap<(u64, u64), u64>,
-    ) -> u64 {
-        if max_depth == cur_depth {
-            return 1;
+    impl Predicate {
+        pub fn matches(self, stone: u64) -> bool {
+            let digits = stone.checked_ilog10().unwrap_or(0) + 1;
+            match self {
+                Predicate::Always => true,
+                Predicate::Equals(n) => stone == n,
+                Predicate::EvenDigits => digits.is_multiple_of(2),
+                Predicate::OddDigits => !digits.is_multiple_of(2),
+                Predicate::DivisibleBy(n) => stone.is_multiple_of(n),
+            }
         }
+    }
 
-        let new_stone1;
-        let mut new_stone2 = None;
-
-        if stone == 0 {
-            new_stone1 = 1;
-        } else {
-            let stone_string = stone.to_string();
-            if stone_string.len() % 2 == 0 {
-                let (left, right) = stone_string.split_at(stone_string.len() / 2);
-                let mut left = left.trim_start_matches("0");
-                if left.is_empty() {
-                    left = "0";
-                }
-                let mut right = right.trim_start_matches("0");
-                if right.is_empty() {
-                    right = "0";
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Transform {
+        Replace(u64),
+        /// Split into the left and right halves of the digits. Stones with an odd count of
+        /// digits can't be split and are left as they are.
+        Split,
+        Multiply(u64),
+        Add(u64),
+    }
+
+    impl Transform {
+        pub fn apply(self, stone: u64) -> Result<(u64, Option<u64>), Overflow> {
+            let overflow = Overflow::Value(stone);
+            match self {
+                Transform::Replace(n) => Ok((n, None)),
+                Transform::Split => {
+                    Ok(split_digits(stone)
+                        .map_or((stone, None), |(left, right)| (left, Some(right))))
                 }
-                new_stone1 = left.parse().unwrap();
-                new_stone2 = Some(right.parse().unwrap());
-            } else {
-                new_stone1 = stone * 2024;
+                Transform::Multiply(n) => Ok((stone.checked_mul(n).ok_or(overflow)?, None)),
+                Transform::Add(n) => Ok((stone.checked_add(n).ok_or(overflow)?, None)),
             }
         }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct StoneRule {
+        pub predicate: Predicate,
+        pub transform: Transform,
+    }
+
+    impl std::fmt::Display for StoneRule {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.predicate {
+                Predicate::Always => write!(f, "always")?,
+                Predicate::Equals(n) => write!(f, "{n}")?,
+                Predicate::EvenDigits => write!(f, "even-digits")?,
+                Predicate::OddDigits => write!(f, "odd-digits")?,
+                Predicate::DivisibleBy(n) => write!(f, "divisible {n}")?,
+            }
+            write!(f, " -> ")?;
+            match self.transform {
+                Transform::Replace(n) => write!(f, "{n}"),
+                Transform::Split => write!(f, "split"),
+                Transform::Multiply(n) => write!(f, "* {n}"),
+                Transform::Add(n) => write!(f, "+ {n}"),
+            }
+        }
+    }
 
-        let mut tot = 0;
+    /// Rules in priority order, each stone changing by the first rule it matches. Stones matching
+    /// no rule stay as they are.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RuleSet(pub Vec<StoneRule>);
 
-        if let Some(known) = lookup.get(&(new_stone1, cur_depth)) {
-            tot += *known;
-        } else {
-            let val = update_stone(new_stone1, max_depth, cur_depth + 1, lookup);
-            lookup.insert((new_stone1, cur_depth), val);
-            tot += val;
+    impl RuleSet {
+        /// The puzzle's rules
+        pub fn aoc() -> Self {
+            RuleSet(vec![
+                StoneRule {
+                    predicate: Predicate::Equals(0),
+                    transform: Transform::Replace(1),
+                },
+                StoneRule {
+                    predicate: Predicate::EvenDigits,
+                    transform: Transform::Split,
+                },
+                StoneRule {
+                    predicate: Predicate::Always,
+                    transform: Transform::Multiply(2024),
+                },
+            ])
         }
 
-        if let Some(new_stone2) = new_stone2 {
-            if let Some(known) = lookup.get(&(new_stone2, cur_depth)) {
-                tot += *known;
-            } else {
-                let val = update_stone(new_stone2, max_depth, cur_depth + 1, lookup);
-                lookup.insert((new_stone2, cur_depth), val);
-                tot += val;
+        /// What one stone becomes after a blink.
+        pub fn blink_stone(&self, stone: u64) -> Result<(u64, Option<u64>), Overflow> {
+            match self.0.iter().find(|rule| rule.predicate.matches(stone)) {
+                Some(rule) => rule.transform.apply(stone),
+                None => Ok((stone, None)),
             }
         }
+    }
+
+    fn parse_predicate(input: &str) -> IResult<&str, Predicate> {
+        alt((
+            value(Predicate::Always, tag("always")),
+            value(Predicate::EvenDigits, tag("even-digits")),
+            value(Predicate::OddDigits, tag("odd-digits")),
+            map(
+                preceded(keyword("divisible"), parse_u64),
+                Predicate::DivisibleBy,
+            ),
+            map(parse_u64, Predicate::Equals),
+        ))(input)
+    }
+
+    fn parse_transform(input: &str) -> IResult<&str, Transform> {
+        alt((
+            value(Transform::Split, tag("split")),
+            map(preceded(keyword("*"), parse_u64), Transform::Multiply),
+            map(preceded(keyword("+"), parse_u64), Transform::Add),
+            map(parse_u64, Transform::Replace),
+        ))(input)
+    }
+
+    /// A keyword, optionally surrounded by spaces.
+    fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+        move |input| delimited(space0, tag(word), space0)(input)
+    }
 
-        tot
+    fn parse_rule(input: &str) -> IResult<&str, StoneRule> {
+        map(
+            separated_pair(
+                parse_predicate,
+                delimited(space0, tag("->"), space0),
+                parse_transform,
+            ),
+            |(predicate, transform)| StoneRule {
+                predicate,
+                transform,
+            },
+        )(input)
     }
 
-    pub fn solve2(input: &Vec<String>) -> u64 {
-        let mut total = 0;
+    /// One rule per line, e.g. `0 -> 1`, `even-digits -> split` or `always -> * 2024`. Blank
+    /// lines and anything after a `#` are ignored.
+    impl std::str::FromStr for RuleSet {
+        type Err = String;
 
-        let mut lookup = HashMap::new();
-        for stone in input {
-            total += update_stone(stone.parse().unwrap(), 75, 0, &mut lookup);
+        fn from_str(input: &str) -> Result<Self, Self::Err> {
+            let mut rules = Vec::new();
+            for (idx, line) in input.lines().enumerate() {
+                let line = line.split('#').next().unwrap().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let (_, rule) = all_consuming(parse_rule)(line)
+                    .map_err(|_| format!("line {}: invalid rule {line:?}", idx + 1))?;
+                rules.push(rule);
+            }
+            Ok(RuleSet(rules))
         }
+    }
+}
+
+fn blink(stones: &Stones, rules: &RuleSet) -> Result<Stones, Overflow> {
+    let mut next = Stones::with_capacity(stones.len());
+    let mut add = |stone: u64, count: u128| -> Result<(), Overflow> {
+        let total = next.entry(stone).or_default();
+        *total = total.checked_add(count).ok_or(Overflow::Count)?;
+        Ok(())
+    };
 
-        total
+    for (stone, count) in stones {
+        let (left, right) = rules.blink_stone(*stone)?;
+        add(left, *count)?;
+        if let Some(right) = right {
+            add(right, *count)?;
+        }
+    }
+
+    Ok(next)
+}
+
+fn count_stones(input: &[u64], blinks: usize, rules: &RuleSet) -> Result<u128, Overflow> {
+    let mut stones = Stones::new();
+    for stone in input {
+        *stones.entry(*stone).or_default() += 1;
     }
+
+    for _ in 0..blinks {
+        stones = blink(&stones, rules)?;
+    }
+
+    stones
+        .values()
+        .try_fold(0u128, |total, count| total.checked_add(*count))
+        .ok_or(Overflow::Count)
+}
+
+fn solve1(input: &[u64]) -> u128 {
+    count_stones(input, 25, &RuleSet::aoc()).unwrap()
+}
+
+fn solve2(input: &[u64]) -> u128 {
+    count_stones(input, 75, &RuleSet::aoc()).unwrap()
 }
 
 fn main() {
     let parsed = parse_input(include_str!("input.txt"));
 
-    println!("Part 1: {}", part1::solve1(&parsed));
-    println!("Part 2: {}", part2::solve2(&parsed));
+    println!("Part 1: {}", solve1(&parsed));
+    println!("Part 2: {}", solve2(&parsed));
+
+    // `--blinks <n> [--rules <file>]` counts the stones after any number of blinks, following
+    // the puzzle's rules unless given a rules file
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--blinks") {
+        let blinks = args
+            .get(idx + 1)
+            .and_then(|blinks| blinks.parse().ok())
+            .expect("Missing or invalid blink count");
+        let rules = match args.iter().position(|arg| arg == "--rules") {
+            Some(idx) => {
+                let path = args.get(idx + 1).expect("Missing rules file");
+                std::fs::read_to_string(path)
+                    .expect("Failed to read rules")
+                    .parse()
+                    .unwrap_or_else(|e| panic!("{e}"))
+            }
+            None => RuleSet::aoc(),
+        };
+        match count_stones(&parsed, blinks, &rules) {
+            Ok(count) => println!("{blinks} blinks: {count}"),
+            Err(e) => println!("{blinks} blinks: {e}"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -130,6 +299,89 @@ mod tests {
     #[test]
     fn test1() {
         let parsed = parse_input(INPUT);
-        assert_eq!(part1::solve1(&parsed), 55312);
+        assert_eq!(solve1(&parsed), 55312);
+    }
+
+    #[test]
+    fn test2() {
+        let parsed = parse_input(INPUT);
+        assert_eq!(count_stones(&parsed, 6, &RuleSet::aoc()), Ok(22));
+        assert_eq!(solve2(&parsed), 65601038650482);
+    }
+
+    #[test]
+    fn test_split_digits() {
+        assert_eq!(split_digits(0), None);
+        assert_eq!(split_digits(7), None);
+        assert_eq!(split_digits(10), Some((1, 0)));
+        assert_eq!(split_digits(1000), Some((10, 0)));
+        assert_eq!(split_digits(253000), Some((253, 0)));
+        assert_eq!(split_digits(512072), Some((512, 72)));
+        assert_eq!(split_digits(999), None);
+        assert_eq!(
+            split_digits(10_000_000_000_000_000_000),
+            Some((1_000_000_000, 0))
+        );
+        assert_eq!(split_digits(u64::MAX), Some((1844674407, 3709551615)));
+    }
+
+    #[test]
+    fn test_many_blinks() {
+        let parsed = parse_input(INPUT);
+        // The number of stones outgrows a u128 long before a thousand blinks
+        assert_eq!(
+            count_stones(&parsed, 1000, &RuleSet::aoc()),
+            Err(Overflow::Count)
+        );
+        assert!(count_stones(&parsed, 150, &RuleSet::aoc()).is_ok());
+
+        assert_eq!(
+            RuleSet::aoc().blink_stone(1_000_000_000_000_000_000),
+            Err(Overflow::Value(1_000_000_000_000_000_000))
+        );
+    }
+
+    #[test]
+    fn test_rule_file() {
+        let rules: RuleSet = include_str!("rules.txt").parse().unwrap();
+        assert_eq!(rules, RuleSet::aoc());
+        let text: Vec<String> = rules.0.iter().map(|rule| rule.to_string()).collect();
+        assert_eq!(text, ["0 -> 1", "even-digits -> split", "always -> * 2024"]);
+    }
+
+    #[test]
+    fn test_rule_variants() {
+        use rules::*;
+
+        let rules: RuleSet = "divisible 3 -> + 1  # comment\n\nodd-digits -> * 11\n7->2"
+            .parse()
+            .unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(
+            rules.0[0],
+            StoneRule {
+                predicate: Predicate::DivisibleBy(3),
+                transform: Transform::Add(1),
+            }
+        );
+        assert_eq!(rules.blink_stone(9), Ok((10, None)));
+        // 7 has an odd count of digits so the earlier rule wins
+        assert_eq!(rules.blink_stone(7), Ok((77, None)));
+        // No rule matches, so it stays the same
+        assert_eq!(rules.blink_stone(10), Ok((10, None)));
+
+        // Splitting everything halves big numbers down to single digits that can't split
+        let rules: RuleSet = "always -> split".parse().unwrap();
+        assert_eq!(rules.blink_stone(123), Ok((123, None)));
+        assert_eq!(count_stones(&[12345678], 3, &rules), Ok(8));
+        assert_eq!(count_stones(&[12345678], 100, &rules), Ok(8));
+
+        // No rules means nothing ever changes
+        assert_eq!(count_stones(&[1, 2, 3], 10, &RuleSet(Vec::new())), Ok(3));
+
+        assert!("0 => 1".parse::<RuleSet>().is_err());
+        assert!("even-digits -> split\nsometimes -> 1"
+            .parse::<RuleSet>()
+            .unwrap_err()
+            .starts_with("line 2"));
     }
 }
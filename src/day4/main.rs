@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use nom::character::complete::{newline, one_of};
 use nom::multi::{many1, separated_list1};
 use nom::IResult;
@@ -6,86 +8,290 @@ fn parse_input(input: &str) -> IResult<&str, Vec<Vec<char>>> {
     separated_list1(newline, many1(one_of("XMAS")))(input)
 }
 
-mod part1 {
-    const VALID_WORD: &[char] = &['X', 'M', 'A', 'S'];
-
-    fn check_next_letter(
-        input: &[Vec<char>],
-        letter_idx: usize,
-        cur_x: usize,
-        cur_y: usize,
-        diff: (isize, isize),
-    ) -> bool {
-        if letter_idx < VALID_WORD.len() {
-            let (Some(new_x), Some(new_y)) = (
-                cur_x.checked_add_signed(diff.0),
-                cur_y.checked_add_signed(diff.1),
-            ) else {
-                return false;
+mod stencil {
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    /// Wildcard cell in a stencil, matches any letter.
+    const WILDCARD: char = '.';
+
+    /// Optional mirror (across the vertical axis) followed by clockwise quarter turns.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Transform {
+        pub reflect: bool,
+        pub quarter_turns: u8,
+    }
+
+    impl Transform {
+        const fn new(reflect: bool, quarter_turns: u8) -> Self {
+            Self {
+                reflect,
+                quarter_turns,
+            }
+        }
+    }
+
+    pub const ROTATIONS: &[Transform] = &[
+        Transform::new(false, 0),
+        Transform::new(false, 1),
+        Transform::new(false, 2),
+        Transform::new(false, 3),
+    ];
+
+    pub const ROTATIONS_AND_REFLECTIONS: &[Transform] = &[
+        Transform::new(false, 0),
+        Transform::new(false, 1),
+        Transform::new(false, 2),
+        Transform::new(false, 3),
+        Transform::new(true, 0),
+        Transform::new(true, 1),
+        Transform::new(true, 2),
+        Transform::new(true, 3),
+    ];
+
+    /// A rectangular 2D pattern where `None` cells match anything.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct Stencil {
+        cells: Vec<Vec<Option<char>>>,
+    }
+
+    impl FromStr for Stencil {
+        type Err = String;
+
+        /// Rows are separated by `/` or newlines, `.` is a wildcard, e.g. `M.S / .A. / M.S`.
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let cells: Vec<Vec<Option<char>>> = s
+                .split(['/', '\n'])
+                .map(|row| row.trim())
+                .filter(|row| !row.is_empty())
+                .map(|row| {
+                    row.chars()
+                        .map(|c| if c == WILDCARD { None } else { Some(c) })
+                        .collect()
+                })
+                .collect();
+
+            let Some(width) = cells.first().map(|row| row.len()) else {
+                return Err("Empty stencil".to_string());
             };
+            if cells.iter().any(|row| row.len() != width) {
+                return Err(format!("Stencil rows must all be {width} wide: {s:?}"));
+            }
+
+            Ok(Self { cells })
+        }
+    }
+
+    impl Stencil {
+        pub fn width(&self) -> usize {
+            self.cells[0].len()
+        }
+
+        pub fn height(&self) -> usize {
+            self.cells.len()
+        }
+
+        /// Cells that must match a letter, as `(dx, dy, letter)` offsets from the top left.
+        pub fn fixed_cells(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+            self.cells.iter().enumerate().flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter_map(move |(x, cell)| cell.map(|c| (x, y, c)))
+            })
+        }
 
-            return new_x < input[0].len()
-                && new_y < input.len()
-                && input[new_y][new_x] == VALID_WORD[letter_idx]
-                && check_next_letter(input, letter_idx + 1, new_x, new_y, diff);
+        fn rotate_right(&self) -> Self {
+            let cells = (0..self.width())
+                .map(|x| (0..self.height()).rev().map(|y| self.cells[y][x]).collect())
+                .collect();
+            Self { cells }
         }
 
-        true
+        pub fn transform(&self, transform: Transform) -> Self {
+            let mut stencil = self.clone();
+            if transform.reflect {
+                for row in stencil.cells.iter_mut() {
+                    row.reverse();
+                }
+            }
+            for _ in 0..transform.quarter_turns % 4 {
+                stencil = stencil.rotate_right();
+            }
+            stencil
+        }
+
+        fn matches_at(&self, grid: &[Vec<char>], x: usize, y: usize) -> bool {
+            self.fixed_cells()
+                .all(|(dx, dy, letter)| grid[y + dy][x + dx] == letter)
+        }
     }
 
-    pub fn solve1(input: &[Vec<char>]) -> i64 {
-        let mut total = 0;
-        for (y, row) in input.iter().enumerate() {
-            for (x, letter) in row.iter().enumerate() {
-                if *letter == VALID_WORD[0] {
-                    for diff_x in -1..=1 {
-                        for diff_y in -1..=1 {
-                            if check_next_letter(input, 1, x, y, (diff_x, diff_y)) {
-                                total += 1;
-                            }
-                        }
+    /// Top left corner of a placement, along with the transform of the stencil that matched.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Match {
+        pub x: usize,
+        pub y: usize,
+        pub transform: Transform,
+    }
+
+    impl Match {
+        /// Grid cells covered by the non-wildcard letters of `stencil` at this placement.
+        pub fn cells(&self, stencil: &Stencil) -> Vec<(usize, usize)> {
+            stencil
+                .transform(self.transform)
+                .fixed_cells()
+                .map(|(dx, dy, _)| (self.x + dx, self.y + dy))
+                .collect()
+        }
+    }
+
+    /// Find every placement of `stencil` under `transforms`. Transforms which produce the same
+    /// stencil (e.g. a reflection of a symmetric shape) are only searched once.
+    pub fn find_matches(
+        grid: &[Vec<char>],
+        stencil: &Stencil,
+        transforms: &[Transform],
+    ) -> Vec<Match> {
+        let mut seen = HashSet::new();
+        let mut matches = Vec::new();
+
+        for transform in transforms {
+            let transformed = stencil.transform(*transform);
+            if !seen.insert(transformed.clone()) {
+                continue;
+            }
+
+            let grid_height = grid.len();
+            let grid_width = grid.first().map(|row| row.len()).unwrap_or(0);
+            if transformed.height() > grid_height || transformed.width() > grid_width {
+                continue;
+            }
+
+            for y in 0..=grid_height - transformed.height() {
+                for x in 0..=grid_width - transformed.width() {
+                    if transformed.matches_at(grid, x, y) {
+                        matches.push(Match {
+                            x,
+                            y,
+                            transform: *transform,
+                        });
                     }
                 }
             }
         }
 
-        total
+        matches
+    }
+}
+
+const PART1_STENCILS: &[&str] = &["XMAS", "X... / .M.. / ..A. / ...S"];
+const PART2_STENCIL: &str = "M.S / .A. / M.S";
+
+fn count_stencils(
+    input: &[Vec<char>],
+    stencils: &[&str],
+    transforms: &[stencil::Transform],
+) -> i64 {
+    stencils
+        .iter()
+        .map(|s| {
+            let stencil: stencil::Stencil = s.parse().expect("Invalid stencil");
+            stencil::find_matches(input, &stencil, transforms).len() as i64
+        })
+        .sum()
+}
+
+fn matched_cells(
+    input: &[Vec<char>],
+    stencils: &[&str],
+    transforms: &[stencil::Transform],
+) -> HashSet<(usize, usize)> {
+    let mut cells = HashSet::new();
+    for s in stencils {
+        let stencil: stencil::Stencil = s.parse().expect("Invalid stencil");
+        for m in stencil::find_matches(input, &stencil, transforms) {
+            cells.extend(m.cells(&stencil));
+        }
     }
+    cells
+}
+
+pub fn solve1(input: &[Vec<char>]) -> i64 {
+    count_stencils(input, PART1_STENCILS, stencil::ROTATIONS)
 }
 
 pub fn solve2(input: &[Vec<char>]) -> i64 {
-    let mut total = 0;
-    for (y, row) in input.iter().enumerate() {
-        for (x, letter) in row.iter().enumerate() {
-            if *letter == 'A' {
-                // Validate both diagonals are valid (not against edge of input)
-                if x.checked_add_signed(-1).is_some()
-                    && x + 1 < input[0].len()
-                    && y.checked_add_signed(-1).is_some()
-                    && y + 1 < input.len()
-                {
-                    let diag1 = (input[y - 1][x - 1], input[y + 1][x + 1]);
-                    let diag2 = (input[y - 1][x + 1], input[y + 1][x - 1]);
-                    if ((diag1.0 == 'M' && diag1.1 == 'S') || (diag1.0 == 'S' && diag1.1 == 'M'))
-                        && ((diag2.0 == 'M' && diag2.1 == 'S')
-                            || (diag2.0 == 'S' && diag2.1 == 'M'))
-                    {
-                        total += 1;
-                    }
+    count_stencils(input, &[PART2_STENCIL], stencil::ROTATIONS_AND_REFLECTIONS)
+}
+
+pub fn part1_cells(input: &[Vec<char>]) -> HashSet<(usize, usize)> {
+    matched_cells(input, PART1_STENCILS, stencil::ROTATIONS)
+}
+
+pub fn part2_cells(input: &[Vec<char>]) -> HashSet<(usize, usize)> {
+    matched_cells(input, &[PART2_STENCIL], stencil::ROTATIONS_AND_REFLECTIONS)
+}
+
+mod render {
+    use std::collections::HashSet;
+
+    const HIGHLIGHT: &str = "\x1b[1;32m";
+    const RESET: &str = "\x1b[0m";
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Style {
+        Plain,
+        Ansi,
+    }
+
+    /// Draw the grid with every letter outside `highlighted` replaced by `.`, as in the puzzle
+    /// text. With `Style::Ansi` the remaining letters are also coloured.
+    pub fn render(
+        grid: &[Vec<char>],
+        highlighted: &HashSet<(usize, usize)>,
+        style: Style,
+    ) -> String {
+        let mut output = String::new();
+        for (y, row) in grid.iter().enumerate() {
+            for (x, letter) in row.iter().enumerate() {
+                if !highlighted.contains(&(x, y)) {
+                    output.push('.');
+                } else if style == Style::Ansi {
+                    output.push_str(HIGHLIGHT);
+                    output.push(*letter);
+                    output.push_str(RESET);
+                } else {
+                    output.push(*letter);
                 }
             }
+            output.push('\n');
         }
+        output
     }
-
-    total
 }
 
 fn main() {
     let parsed = parse_input(include_str!("input.txt")).expect("Failed to parse input");
     assert!(parsed.0.is_empty());
 
-    println!("Part 1: {}", part1::solve1(&parsed.1));
+    println!("Part 1: {}", solve1(&parsed.1));
     println!("Part 2: {}", solve2(&parsed.1));
+
+    if std::env::args().any(|arg| arg == "--render") {
+        let style = if std::env::args().any(|arg| arg == "--plain") {
+            render::Style::Plain
+        } else {
+            render::Style::Ansi
+        };
+        println!(
+            "{}",
+            render::render(&parsed.1, &part1_cells(&parsed.1), style)
+        );
+        println!(
+            "{}",
+            render::render(&parsed.1, &part2_cells(&parsed.1), style)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -108,7 +314,7 @@ mod tests {
     fn solve1_test() {
         let parsed = parse_input(INPUT).unwrap();
         assert!(parsed.0.is_empty());
-        assert_eq!(part1::solve1(&parsed.1), 18);
+        assert_eq!(solve1(&parsed.1), 18);
     }
 
     #[test]
@@ -117,4 +323,80 @@ mod tests {
         assert!(parsed.0.is_empty());
         assert_eq!(solve2(&parsed.1), 9);
     }
+
+    #[test]
+    fn stencil_transform_test() {
+        let stencil: stencil::Stencil = "M.S/.A./M.S".parse().unwrap();
+        let parsed = parse_input(INPUT).unwrap();
+
+        // Reflections of the X-MAS are already covered by its rotations
+        assert_eq!(
+            stencil::find_matches(&parsed.1, &stencil, stencil::ROTATIONS),
+            stencil::find_matches(&parsed.1, &stencil, stencil::ROTATIONS_AND_REFLECTIONS)
+        );
+        assert_eq!(
+            stencil::find_matches(&parsed.1, &stencil, &stencil::ROTATIONS[..1]).len(),
+            2
+        );
+
+        let rotated = stencil.transform(stencil::ROTATIONS[1]);
+        assert_eq!(rotated, "M.M/.A./S.S".parse().unwrap());
+    }
+
+    #[test]
+    fn render_test() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert!(parsed.0.is_empty());
+
+        let part1 = render::render(&parsed.1, &part1_cells(&parsed.1), render::Style::Plain);
+        assert_eq!(
+            part1,
+            "....XXMAS.\n\
+             .SAMXMS...\n\
+             ...S..A...\n\
+             ..A.A.MS.X\n\
+             XMASAMX.MM\n\
+             X.....XA.A\n\
+             S.S.S.S.SS\n\
+             .A.A.A.A.A\n\
+             ..M.M.M.MM\n\
+             .X.X.XMASX\n"
+        );
+
+        let part2 = render::render(&parsed.1, &part2_cells(&parsed.1), render::Style::Plain);
+        assert_eq!(
+            part2,
+            ".M.S......\n\
+             ..A..MSMS.\n\
+             .M.S.MAA..\n\
+             ..A.ASMSM.\n\
+             .M.S.M....\n\
+             ..........\n\
+             S.S.S.S.S.\n\
+             .A.A.A.A..\n\
+             M.M.M.M.M.\n\
+             ..........\n"
+        );
+
+        let ansi = render::render(&parsed.1, &part2_cells(&parsed.1), render::Style::Ansi);
+        assert!(ansi.starts_with(".\x1b[1;32mM\x1b[0m."));
+    }
+
+    #[test]
+    fn stencil_custom_shape_test() {
+        let grid: Vec<Vec<char>> = ["XMA", "SXM", "ASX"]
+            .iter()
+            .map(|row| row.chars().collect())
+            .collect();
+        let stencil: stencil::Stencil = "XM / .X".parse().unwrap();
+
+        let matches = stencil::find_matches(&grid, &stencil, &stencil::ROTATIONS[..1]);
+        assert_eq!(
+            matches.iter().map(|m| (m.x, m.y)).collect::<Vec<_>>(),
+            &[(0, 0), (1, 1)]
+        );
+
+        assert!("XM/X".parse::<stencil::Stencil>().is_err());
+        assert!(" / ".parse::<stencil::Stencil>().is_err());
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 
 use nom::character::complete::{char, i64 as parse_i64, multispace1, newline};
 use nom::multi::separated_list1;
@@ -28,64 +29,476 @@ fn parse_updates(input: &str) -> IResult<&str, Vec<Vec<i64>>> {
 }
 
 fn update_in_order(input: &[i64], depends_on: &DependsOn) -> bool {
-    for (i, page) in input.iter().enumerate() {
-        if let Some(prec) = depends_on.get(page) {
-            for p in prec {
-                if input.iter().any(|v| *v == *p) && !input[..i].iter().any(|v| *v == *p) {
-                    return false;
+    let position: HashMap<i64, usize> = input.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    input.iter().enumerate().all(|(i, page)| {
+        depends_on.get(page).is_none_or(|prec| {
+            prec.iter()
+                .all(|p| position.get(p).is_none_or(|idx| *idx < i))
+        })
+    })
+}
+
+/// The ordering rules restricted to the pages of a single update.
+struct PageGraph {
+    pages: Vec<i64>,
+    /// `after[i]` holds the indexes of pages which must come after `pages[i]`
+    after: Vec<Vec<usize>>,
+    /// `before[i]` holds the indexes of pages which must come before `pages[i]`
+    before: Vec<Vec<usize>>,
+}
+
+impl PageGraph {
+    fn new(update: &[i64], depends_on: &DependsOn) -> Self {
+        let index: HashMap<i64, usize> = update.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+        let mut after = vec![Vec::new(); update.len()];
+        let mut before = vec![Vec::new(); update.len()];
+
+        for (i, page) in update.iter().enumerate() {
+            for p in depends_on.get(page).into_iter().flatten() {
+                if let Some(&j) = index.get(p) {
+                    after[j].push(i);
+                    before[i].push(j);
                 }
             }
         }
+
+        Self {
+            pages: update.into(),
+            after,
+            before,
+        }
     }
-    true
-}
 
-fn fix_order(input: &[i64], depends_on: &DependsOn) -> Vec<i64> {
-    let mut fixed: Vec<i64> = input.into();
+    /// Kahn's algorithm, ties broken by the original update order. Returns `None` on a cycle.
+    fn kahn_sort(&self) -> Option<Vec<i64>> {
+        let mut in_degree: Vec<usize> = self.before.iter().map(|b| b.len()).collect();
+        let mut ready: BinaryHeap<Reverse<usize>> = in_degree
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| **d == 0)
+            .map(|(i, _)| Reverse(i))
+            .collect();
 
-    'outer: loop {
-        for i in 0..fixed.len() {
-            let val = fixed[i];
-            if let Some(prec) = depends_on.get(&val) {
-                for p in prec {
-                    if let Some(idx) = fixed.iter().position(|v| v == p) {
-                        if idx > i {
-                            fixed.remove(idx);
-                            fixed.insert(i, *p);
-                            continue 'outer;
-                        }
+        let mut sorted = Vec::with_capacity(self.pages.len());
+        while let Some(Reverse(i)) = ready.pop() {
+            sorted.push(self.pages[i]);
+            for j in &self.after[i] {
+                in_degree[*j] -= 1;
+                if in_degree[*j] == 0 {
+                    ready.push(Reverse(*j));
+                }
+            }
+        }
+
+        (sorted.len() == self.pages.len()).then_some(sorted)
+    }
+
+    /// Pages forming a cycle, in rule order, if the graph has one.
+    fn find_cycle(&self) -> Option<Vec<i64>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            OnStack,
+            Done,
+        }
+
+        fn visit(graph: &PageGraph, i: usize, state: &mut [State], stack: &mut Vec<usize>) -> bool {
+            state[i] = State::OnStack;
+            stack.push(i);
+            for j in &graph.after[i] {
+                let next_state = state[*j];
+                match next_state {
+                    State::OnStack => {
+                        let start = stack.iter().position(|s| s == j).unwrap();
+                        stack.drain(..start);
+                        return true;
                     }
+                    State::Unvisited if visit(graph, *j, state, stack) => return true,
+                    _ => (),
+                }
+            }
+            stack.pop();
+            state[i] = State::Done;
+            false
+        }
+
+        let mut state = vec![State::Unvisited; self.pages.len()];
+        let mut stack = Vec::new();
+        for i in 0..self.pages.len() {
+            if state[i] == State::Unvisited && visit(self, i, &mut state, &mut stack) {
+                return Some(stack.into_iter().map(|i| self.pages[i]).collect());
+            }
+        }
+        None
+    }
+
+    /// First pair of pages with no (transitive) rule between them, if the order isn't total.
+    fn find_unordered_pair(&self) -> Option<(i64, i64)> {
+        let mut in_degree: Vec<usize> = self.before.iter().map(|b| b.len()).collect();
+        let mut ready: Vec<usize> = (0..self.pages.len())
+            .filter(|i| in_degree[*i] == 0)
+            .collect();
+
+        // The order is total exactly when Kahn's algorithm never has a choice to make
+        while let Some(i) = ready.pop() {
+            if let Some(j) = ready.last() {
+                return Some((self.pages[*j], self.pages[i]));
+            }
+            for j in &self.after[i] {
+                in_degree[*j] -= 1;
+                if in_degree[*j] == 0 {
+                    ready.push(*j);
                 }
             }
         }
-        break;
+        None
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RuleError {
+    DuplicatePage { update: usize, page: i64 },
+    UnruledPage { update: usize, page: i64 },
+    Cycle { update: usize, pages: Vec<i64> },
+    Ambiguous { update: usize, pages: (i64, i64) },
+}
 
-    fixed
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::DuplicatePage { update, page } => {
+                write!(f, "update {update}: page {page} appears more than once")
+            }
+            RuleError::UnruledPage { update, page } => {
+                write!(f, "update {update}: page {page} has no ordering rules")
+            }
+            RuleError::Cycle { update, pages } => {
+                let pages: Vec<String> = pages.iter().map(|p| p.to_string()).collect();
+                write!(
+                    f,
+                    "update {update}: rules form a cycle {}",
+                    pages.join(" -> ")
+                )
+            }
+            RuleError::Ambiguous { update, pages } => write!(
+                f,
+                "update {update}: no rule orders {} and {}, middle page is ambiguous",
+                pages.0, pages.1
+            ),
+        }
+    }
 }
 
-fn solve1(updates: &[Vec<i64>], depends_on: &DependsOn) -> i64 {
+fn find_duplicate(update: &[i64]) -> Option<i64> {
+    let mut seen = HashSet::new();
+    update.iter().find(|p| !seen.insert(**p)).copied()
+}
+
+/// Check every update against the rules, collecting all problems found rather than stopping at
+/// the first one.
+fn validate(updates: &[Vec<i64>], depends_on: &DependsOn) -> Vec<RuleError> {
+    let ruled_pages: HashSet<i64> = depends_on
+        .iter()
+        .flat_map(|(page, prec)| std::iter::once(page).chain(prec))
+        .copied()
+        .collect();
+
+    let mut errors = Vec::new();
+    for (update_idx, update) in updates.iter().enumerate() {
+        if let Some(page) = find_duplicate(update) {
+            errors.push(RuleError::DuplicatePage {
+                update: update_idx,
+                page,
+            });
+            continue;
+        }
+
+        for page in update.iter().filter(|p| !ruled_pages.contains(p)) {
+            errors.push(RuleError::UnruledPage {
+                update: update_idx,
+                page: *page,
+            });
+        }
+
+        let graph = PageGraph::new(update, depends_on);
+        if let Some(pages) = graph.find_cycle() {
+            errors.push(RuleError::Cycle {
+                update: update_idx,
+                pages,
+            });
+        } else if let Some(pages) = graph.find_unordered_pair() {
+            errors.push(RuleError::Ambiguous {
+                update: update_idx,
+                pages,
+            });
+        }
+    }
+
+    errors
+}
+
+fn fix_order(input: &[i64], depends_on: &DependsOn) -> Option<Vec<i64>> {
+    PageGraph::new(input, depends_on).kahn_sort()
+}
+
+/// Ordering rules which can be added to and removed from over time, with an index of which pages
+/// must transitively precede which kept up to date as the rules change.
+#[derive(Debug, Default, Clone)]
+struct OrderingRules {
+    depends_on: DependsOn,
+    /// Inverse of `depends_on`, the pages each page must come before
+    successors: HashMap<i64, HashSet<i64>>,
+    /// Every page reachable by following one or more rules
+    reachable: HashMap<i64, HashSet<i64>>,
+    /// Inverse of `reachable`
+    reached_by: HashMap<i64, HashSet<i64>>,
+}
+
+impl From<&DependsOn> for OrderingRules {
+    fn from(depends_on: &DependsOn) -> Self {
+        let mut rules = Self::default();
+        for (after, prec) in depends_on {
+            for before in prec {
+                rules.add_rule(*before, *after);
+            }
+        }
+        rules
+    }
+}
+
+impl OrderingRules {
+    /// Require `before` to be printed before `after`. Returns false if the rule already existed.
+    fn add_rule(&mut self, before: i64, after: i64) -> bool {
+        if !self.successors.entry(before).or_default().insert(after) {
+            return false;
+        }
+        self.depends_on.entry(after).or_default().insert(before);
+
+        if self.precedes(before, after) {
+            return true;
+        }
+
+        // Everything which reaches `before` now also reaches `after` and everything after it
+        let mut sources = self.reached_by.get(&before).cloned().unwrap_or_default();
+        sources.insert(before);
+        let mut targets = self.reachable.get(&after).cloned().unwrap_or_default();
+        targets.insert(after);
+
+        for source in &sources {
+            let reachable = self.reachable.entry(*source).or_default();
+            for target in &targets {
+                if reachable.insert(*target) {
+                    self.reached_by.entry(*target).or_default().insert(*source);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Drop the rule between `before` and `after`. Returns false if there was no such rule.
+    // The puzzle never drops a rule, only the tests exercise this
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn remove_rule(&mut self, before: i64, after: i64) -> bool {
+        if !self
+            .successors
+            .get_mut(&before)
+            .is_some_and(|s| s.remove(&after))
+        {
+            return false;
+        }
+        if let Some(prec) = self.depends_on.get_mut(&after) {
+            prec.remove(&before);
+            if prec.is_empty() {
+                self.depends_on.remove(&after);
+            }
+        }
+
+        // Only pages which could reach `before` can have lost anything, recompute just those
+        let mut affected = self.reached_by.get(&before).cloned().unwrap_or_default();
+        affected.insert(before);
+
+        for source in affected {
+            let reachable = self.search_from(source);
+            let old = self.reachable.insert(source, reachable).unwrap_or_default();
+            for lost in old.difference(&self.reachable[&source]) {
+                if let Some(reached_by) = self.reached_by.get_mut(lost) {
+                    reached_by.remove(&source);
+                }
+            }
+        }
+
+        true
+    }
+
+    fn search_from(&self, page: i64) -> HashSet<i64> {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<i64> = self
+            .successors
+            .get(&page)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        while let Some(next) = stack.pop() {
+            if seen.insert(next) {
+                stack.extend(self.successors.get(&next).into_iter().flatten());
+            }
+        }
+        seen
+    }
+
+    /// `before` must come before `after` through some chain of rules.
+    fn precedes(&self, before: i64, after: i64) -> bool {
+        self.reachable
+            .get(&before)
+            .is_some_and(|r| r.contains(&after))
+    }
+
+    fn depends_on(&self) -> &DependsOn {
+        &self.depends_on
+    }
+
+    /// Rules only apply between pages of the same update, so this uses the direct rules rather
+    /// than the transitive index.
+    fn check(&self, update: &[i64]) -> bool {
+        update_in_order(update, &self.depends_on)
+    }
+
+    /// The update sorted by its rules, or the pages of a cycle if it can't be sorted.
+    fn fix(&self, update: &[i64]) -> Result<Vec<i64>, Vec<i64>> {
+        if let Some(fixed) = fix_order(update, &self.depends_on) {
+            return Ok(fixed);
+        }
+
+        // Only pages which reach themselves can be on a cycle, so search just those
+        let cyclic: Vec<i64> = update
+            .iter()
+            .copied()
+            .filter(|p| self.precedes(*p, *p))
+            .collect();
+        Err(PageGraph::new(&cyclic, &self.depends_on)
+            .find_cycle()
+            .expect("Kahn's algorithm only fails on a cycle"))
+    }
+}
+
+/// Export the rules as a Graphviz digraph with an edge from each page to the pages that must
+/// follow it. With an `update` the graph is restricted to its pages and rules broken by the
+/// update are drawn in red. With `reduce` any edge implied by a longer path is dropped, unless it
+/// is one of the broken rules. Pages on a cycle keep just enough edges to still reach each other.
+fn to_dot(depends_on: &DependsOn, update: Option<&[i64]>, reduce: bool) -> String {
+    let nodes: Vec<i64> = match update {
+        Some(update) => update.into(),
+        None => {
+            let pages: BTreeSet<i64> = depends_on
+                .iter()
+                .flat_map(|(page, prec)| std::iter::once(page).chain(prec))
+                .copied()
+                .collect();
+            pages.into_iter().collect()
+        }
+    };
+    let node_set: HashSet<i64> = nodes.iter().copied().collect();
+
+    let mut successors: BTreeMap<i64, BTreeSet<i64>> = BTreeMap::new();
+    for (after, prec) in depends_on {
+        for before in prec {
+            if node_set.contains(after) && node_set.contains(before) {
+                successors.entry(*before).or_default().insert(*after);
+            }
+        }
+    }
+
+    let position: HashMap<i64, usize> = update
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (*p, i))
+        .collect();
+    let out_of_order = |before: i64, after: i64| matches!((position.get(&before), position.get(&after)), (Some(b), Some(a)) if a < b);
+
+    fn reachable_without(successors: &BTreeMap<i64, BTreeSet<i64>>, from: i64, to: i64) -> bool {
+        let mut stack: Vec<i64> = successors[&from]
+            .iter()
+            .copied()
+            .filter(|s| *s != to)
+            .collect();
+        // Coming back round to `from` would only lead to the edge being tested
+        let mut seen = HashSet::from([from]);
+        while let Some(page) = stack.pop() {
+            if page == to {
+                return true;
+            }
+            if seen.insert(page) {
+                stack.extend(successors.get(&page).into_iter().flatten());
+            }
+        }
+        false
+    }
+
+    // Dropping edges one at a time against what's left keeps every page reachable from the same
+    // pages as before, which also reduces cycles rather than dropping every edge on them
+    if reduce {
+        let edges: Vec<(i64, i64)> = successors
+            .iter()
+            .flat_map(|(before, afters)| afters.iter().map(|after| (*before, *after)))
+            .collect();
+        for (before, after) in edges {
+            if !out_of_order(before, after) && reachable_without(&successors, before, after) {
+                successors.get_mut(&before).unwrap().remove(&after);
+            }
+        }
+    }
+
+    let mut dot = String::from("digraph rules {\n");
+    for node in &nodes {
+        dot.push_str(&format!("    {node};\n"));
+    }
+    for (before, afters) in &successors {
+        for after in afters {
+            if out_of_order(*before, *after) {
+                dot.push_str(&format!("    {before} -> {after} [color=red];\n"));
+            } else {
+                dot.push_str(&format!("    {before} -> {after};\n"));
+            }
+        }
+    }
+    dot.push_str("}\n");
+
+    dot
+}
+
+fn solve1(updates: &[Vec<i64>], rules: &OrderingRules) -> i64 {
     let mut total = 0;
     for update in updates {
-        if update_in_order(update, depends_on) {
+        if rules.check(update) {
             total += update[update.len() / 2];
         }
     }
     total
 }
 
-fn solve2(updates: &[Vec<i64>], depends_on: &DependsOn) -> i64 {
+fn solve2(updates: &[Vec<i64>], rules: &OrderingRules) -> Result<i64, RuleError> {
     let mut total = 0;
-    for update in updates {
-        if !update_in_order(update, depends_on) {
-            let dup_check: HashSet<&i64> = HashSet::from_iter(update);
-            assert_eq!(update.len(), dup_check.len());
-            let fixed = fix_order(update, depends_on);
+    for (update_idx, update) in updates.iter().enumerate() {
+        if !rules.check(update) {
+            if let Some(page) = find_duplicate(update) {
+                return Err(RuleError::DuplicatePage {
+                    update: update_idx,
+                    page,
+                });
+            }
+            let fixed = rules.fix(update).map_err(|pages| RuleError::Cycle {
+                update: update_idx,
+                pages,
+            })?;
 
             total += fixed[fixed.len() / 2];
         }
     }
-    total
+    Ok(total)
 }
 
 fn main() {
@@ -93,8 +506,25 @@ fn main() {
     assert!(parsed.0.is_empty());
     let parsed = parsed.1;
 
-    println!("Part 1: {}", solve1(&parsed.1, &parsed.0));
-    println!("Part 2: {}", solve2(&parsed.1, &parsed.0));
+    for error in validate(&parsed.1, &parsed.0) {
+        eprintln!("Warning: {error}");
+    }
+
+    let rules = OrderingRules::from(&parsed.0);
+    println!("Part 1: {}", solve1(&parsed.1, &rules));
+    match solve2(&parsed.1, &rules) {
+        Ok(total) => println!("Part 2: {total}"),
+        Err(error) => println!("Part 2 failed: {error}"),
+    }
+
+    // `--dot [update index]` prints the reduced rule graph, optionally for a single update
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--dot") {
+        let update = args
+            .get(idx + 1)
+            .map(|i| parsed.1[i.parse::<usize>().expect("Invalid update index")].as_slice());
+        print!("{}", to_dot(rules.depends_on(), update, true));
+    }
 }
 
 #[cfg(test)]
@@ -143,7 +573,7 @@ mod tests {
         assert!(parsed.0.is_empty());
         let (depends_on, updates) = parsed.1;
 
-        assert_eq!(solve1(&updates, &depends_on), 143);
+        assert_eq!(solve1(&updates, &OrderingRules::from(&depends_on)), 143);
     }
 
     #[test]
@@ -152,6 +582,225 @@ mod tests {
         assert!(parsed.0.is_empty());
         let (depends_on, updates) = parsed.1;
 
-        assert_eq!(solve2(&updates, &depends_on), 123);
+        assert_eq!(solve2(&updates, &OrderingRules::from(&depends_on)), Ok(123));
+    }
+
+    #[test]
+    fn test_fix_order() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert!(parsed.0.is_empty());
+        let (depends_on, updates) = parsed.1;
+
+        assert_eq!(
+            fix_order(&updates[3], &depends_on),
+            Some(vec![97, 75, 47, 61, 53])
+        );
+        assert_eq!(fix_order(&updates[4], &depends_on), Some(vec![61, 29, 13]));
+        assert_eq!(
+            fix_order(&updates[5], &depends_on),
+            Some(vec![97, 75, 47, 29, 13])
+        );
+    }
+
+    #[test]
+    fn test_kahn_sort_partial_order() {
+        // 1 before 3 and 2 before 3, with no rule between 1 and 2
+        let mut depends_on: DependsOn = HashMap::new();
+        depends_on.entry(3).or_default().extend([1, 2]);
+
+        assert_eq!(
+            PageGraph::new(&[3, 2, 1], &depends_on).kahn_sort(),
+            Some(vec![2, 1, 3])
+        );
+
+        depends_on.entry(1).or_default().insert(3);
+        assert_eq!(PageGraph::new(&[3, 2, 1], &depends_on).kahn_sort(), None);
+    }
+
+    #[test]
+    fn test_solve2_cyclic_tournament() {
+        // A rule between every pair of 40 pages, in a scrambled direction which forms cycles
+        let pages: Vec<i64> = (1..=40).collect();
+        let mut depends_on: DependsOn = HashMap::new();
+        let mut seed = 0x2545_f491_u64;
+        for (i, before) in pages.iter().enumerate() {
+            for after in &pages[i + 1..] {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let (before, after) = if seed.is_multiple_of(3) {
+                    (after, before)
+                } else {
+                    (before, after)
+                };
+                depends_on.entry(*after).or_default().insert(*before);
+            }
+        }
+
+        let update: Vec<i64> = pages.iter().rev().copied().collect();
+        assert_eq!(fix_order(&update, &depends_on), None);
+        assert!(matches!(
+            solve2(&[update], &OrderingRules::from(&depends_on)),
+            Err(RuleError::Cycle { update: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert!(parsed.0.is_empty());
+        let (depends_on, updates) = parsed.1;
+        assert_eq!(validate(&updates, &depends_on), &[]);
+
+        // 1 before 2, 2 before 3, 3 before 1 and 4 before 5
+        let mut depends_on: DependsOn = HashMap::new();
+        depends_on.entry(2).or_default().insert(1);
+        depends_on.entry(3).or_default().insert(2);
+        depends_on.entry(1).or_default().insert(3);
+        depends_on.entry(5).or_default().insert(4);
+
+        let updates = vec![
+            vec![1, 2],
+            vec![3, 1, 2],
+            vec![4, 5, 4],
+            vec![4, 5, 6],
+            vec![1, 4],
+        ];
+        assert_eq!(
+            validate(&updates, &depends_on),
+            &[
+                RuleError::Cycle {
+                    update: 1,
+                    pages: vec![3, 1, 2]
+                },
+                RuleError::DuplicatePage { update: 2, page: 4 },
+                RuleError::UnruledPage { update: 3, page: 6 },
+                RuleError::Ambiguous {
+                    update: 3,
+                    pages: (4, 6)
+                },
+                RuleError::Ambiguous {
+                    update: 4,
+                    pages: (1, 4)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_solve2_cycle() {
+        let mut depends_on: DependsOn = HashMap::new();
+        depends_on.entry(1).or_default().insert(2);
+        depends_on.entry(2).or_default().insert(1);
+
+        assert_eq!(
+            solve2(&[vec![1, 2]], &OrderingRules::from(&depends_on)),
+            Err(RuleError::Cycle {
+                update: 0,
+                pages: vec![1, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert!(parsed.0.is_empty());
+        let (depends_on, updates) = parsed.1;
+
+        assert_eq!(
+            to_dot(&depends_on, Some(&updates[4]), false),
+            "digraph rules {\n    61;\n    13;\n    29;\n    29 -> 13 [color=red];\n    61 -> 13;\n    61 -> 29;\n}\n"
+        );
+        // 61 -> 13 is implied by 61 -> 29 -> 13
+        assert_eq!(
+            to_dot(&depends_on, Some(&updates[4]), true),
+            "digraph rules {\n    61;\n    13;\n    29;\n    29 -> 13 [color=red];\n    61 -> 29;\n}\n"
+        );
+        assert_eq!(
+            to_dot(&depends_on, Some(&updates[0]), true),
+            "digraph rules {\n    75;\n    47;\n    61;\n    53;\n    29;\n    47 -> 61;\n    53 -> 29;\n    61 -> 53;\n    75 -> 47;\n}\n"
+        );
+
+        let full = to_dot(&depends_on, None, true);
+        assert_eq!(full.lines().filter(|l| l.contains("->")).count(), 6);
+        assert!(!full.contains("color=red"));
+    }
+
+    #[test]
+    fn test_to_dot_cycle() {
+        // 1 -> 2 -> 3 -> 1 with 1 -> 3 and 1 -> 4 implied by the cycle and 3 -> 4
+        let mut depends_on: DependsOn = HashMap::new();
+        depends_on.entry(2).or_default().insert(1);
+        depends_on.entry(3).or_default().extend([1, 2]);
+        depends_on.entry(1).or_default().insert(3);
+        depends_on.entry(4).or_default().extend([1, 3]);
+
+        assert_eq!(
+            to_dot(&depends_on, None, true),
+            "digraph rules {\n    1;\n    2;\n    3;\n    4;\n    1 -> 2;\n    2 -> 3;\n    3 -> 1;\n    3 -> 4;\n}\n"
+        );
+        // Broken rules are always kept, and imply 1 -> 4 through 3
+        assert_eq!(
+            to_dot(&depends_on, Some(&[3, 1, 4]), true),
+            "digraph rules {\n    3;\n    1;\n    4;\n    1 -> 3 [color=red];\n    3 -> 1;\n    3 -> 4;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_ordering_rules() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert!(parsed.0.is_empty());
+        let (depends_on, updates) = parsed.1;
+
+        let mut rules = OrderingRules::from(&depends_on);
+        for update in &updates {
+            assert_eq!(rules.check(update), update_in_order(update, &depends_on));
+        }
+        assert!(rules.precedes(97, 13));
+        assert!(!rules.precedes(13, 97));
+
+        // [61, 13, 29] is only broken by 29|13
+        assert!(!rules.check(&updates[4]));
+        assert!(rules.remove_rule(29, 13));
+        assert!(!rules.remove_rule(29, 13));
+        assert!(rules.check(&updates[4]));
+        // 29 has no other rules, but 61 still has its own rule for 13
+        assert!(!rules.precedes(29, 13));
+        assert!(rules.precedes(61, 13));
+        assert!(!rules.check(&[13, 61, 29]));
+
+        assert!(rules.add_rule(29, 13));
+        assert!(!rules.add_rule(29, 13));
+        assert!(rules.precedes(29, 13));
+        assert!(!rules.check(&updates[4]));
+        assert_eq!(rules.fix(&updates[4]), Ok(vec![61, 29, 13]));
+    }
+
+    #[test]
+    fn test_ordering_rules_cycle() {
+        let mut rules = OrderingRules::default();
+        rules.add_rule(1, 2);
+        rules.add_rule(2, 3);
+        assert!(rules.precedes(1, 3));
+        assert!(!rules.precedes(3, 3));
+        // Rules only apply within an update, 1|2|3 doesn't order 1 and 3 on their own
+        assert!(rules.check(&[3, 1]));
+
+        rules.add_rule(3, 1);
+        rules.add_rule(4, 1);
+        assert!(rules.precedes(3, 3));
+        assert!(rules.precedes(2, 1));
+        assert!(!rules.precedes(4, 4));
+        assert!(!rules.check(&[1, 2, 3]));
+        assert_eq!(rules.fix(&[4, 1, 2, 3]), Err(vec![1, 2, 3]));
+        assert_eq!(rules.fix(&[2, 1]), Ok(vec![1, 2]));
+
+        rules.remove_rule(2, 3);
+        assert!(!rules.remove_rule(2, 3));
+        assert!(!rules.precedes(1, 3));
+        assert!(!rules.precedes(3, 3));
+        assert!(rules.precedes(3, 2));
+        assert_eq!(rules.fix(&[1, 2, 3]), Ok(vec![3, 1, 2]));
     }
 }
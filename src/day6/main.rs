@@ -23,27 +23,113 @@ struct Map {
     min_y: i64,
     max_y: i64,
     obstacles: HashSet<Position>,
+    /// Sorted y values of the obstacles in each column
+    by_x: HashMap<i64, Vec<i64>>,
+    /// Sorted x values of the obstacles in each row
+    by_y: HashMap<i64, Vec<i64>>,
 }
 
 impl Map {
     fn in_map(&self, pos: &Position) -> bool {
         pos.x >= self.min_x && pos.x <= self.max_x && pos.y >= self.min_y && pos.y <= self.max_y
     }
+
+    fn add_obstacle(&mut self, pos: Position) {
+        if self.obstacles.insert(pos) {
+            let col = self.by_x.entry(pos.x).or_default();
+            col.insert(col.partition_point(|y| *y < pos.y), pos.y);
+            let row = self.by_y.entry(pos.y).or_default();
+            row.insert(row.partition_point(|x| *x < pos.x), pos.x);
+        }
+    }
+
+    fn remove_obstacle(&mut self, pos: Position) {
+        if self.obstacles.remove(&pos) {
+            self.by_x.entry(pos.x).or_default().retain(|y| *y != pos.y);
+            self.by_y.entry(pos.y).or_default().retain(|x| *x != pos.x);
+        }
+    }
+
+    /// Where the guard comes to a stop in front of the next obstacle it faces, or `None` if it
+    /// walks off the map first.
+    fn next_stop(&self, guard: &Guard) -> Option<Position> {
+        let Position { x, y } = guard.position;
+        let empty = Vec::new();
+        let col = self.by_x.get(&x).unwrap_or(&empty);
+        let row = self.by_y.get(&y).unwrap_or(&empty);
+
+        match guard.direction {
+            Direction::North => {
+                let idx = col.partition_point(|oy| *oy < y);
+                (idx > 0).then(|| Position {
+                    x,
+                    y: col[idx - 1] + 1,
+                })
+            }
+            Direction::South => {
+                let idx = col.partition_point(|oy| *oy <= y);
+                col.get(idx).map(|oy| Position { x, y: oy - 1 })
+            }
+            Direction::West => {
+                let idx = row.partition_point(|ox| *ox < x);
+                (idx > 0).then(|| Position {
+                    x: row[idx - 1] + 1,
+                    y,
+                })
+            }
+            Direction::East => {
+                let idx = row.partition_point(|ox| *ox <= x);
+                row.get(idx).map(|ox| Position { x: ox - 1, y })
+            }
+        }
+    }
+
+    /// Last cell on the map the guard reaches walking in a straight line.
+    fn edge(&self, guard: &Guard) -> Position {
+        let Position { x, y } = guard.position;
+        match guard.direction {
+            Direction::North => Position { x, y: self.min_y },
+            Direction::South => Position { x, y: self.max_y },
+            Direction::West => Position { x: self.min_x, y },
+            Direction::East => Position { x: self.max_x, y },
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Guard {
     position: Position,
     direction: Direction,
+    turn_policy: TurnPolicy,
 }
 
 impl Guard {
-    fn do_move(&mut self, obstacles: &HashSet<Position>) {
-        let candidate_pos = self.position.add_dir(self.direction);
-        if !obstacles.contains(&candidate_pos) {
-            self.position = candidate_pos;
-        } else {
-            self.direction = self.direction.rotate_right();
+    fn turn(&mut self) {
+        self.direction = match self.turn_policy {
+            TurnPolicy::Right => self.direction.rotate_right(),
+            TurnPolicy::Left => self.direction.rotate_left(),
+            TurnPolicy::Reverse => self.direction.reverse(),
+        };
+    }
+}
+
+/// What a guard does on reaching an obstacle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TurnPolicy {
+    Right,
+    Left,
+    Reverse,
+}
+
+impl std::str::FromStr for TurnPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "right" => Ok(TurnPolicy::Right),
+            "left" => Ok(TurnPolicy::Left),
+            "reverse" => Ok(TurnPolicy::Reverse),
+            _ => Err(format!("Unknown turn policy: {s}")),
         }
     }
 }
@@ -65,6 +151,47 @@ impl Direction {
             Direction::West => Self::North,
         }
     }
+
+    fn rotate_left(self) -> Self {
+        self.rotate_right().reverse()
+    }
+
+    fn reverse(self) -> Self {
+        match self {
+            Direction::North => Self::South,
+            Direction::South => Self::North,
+            Direction::East => Self::West,
+            Direction::West => Self::East,
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '^' => Some(Direction::North),
+            'v' => Some(Direction::South),
+            '>' => Some(Direction::East),
+            '<' => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Direction::North => '^',
+            Direction::South => 'v',
+            Direction::East => '>',
+            Direction::West => '<',
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Direction::North => 0,
+            Direction::South => 1,
+            Direction::East => 2,
+            Direction::West => 3,
+        }
+    }
 }
 
 impl From<Direction> for (i64, i64) {
@@ -78,29 +205,241 @@ impl From<Direction> for (i64, i64) {
     }
 }
 
-fn parse_input(input: &str) -> (Guard, Map) {
-    let mut guard = None;
-    let mut obstacles = HashSet::new();
+/// A straight run of the guard's route, `start` and `end` inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: Position,
+    end: Position,
+    direction: Direction,
+}
+
+impl Segment {
+    fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        let mut next = Some(self.start);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = (current != self.end).then(|| current.add_dir(self.direction));
+            Some(current)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Route {
+    segments: Vec<Segment>,
+    looped: bool,
+}
+
+/// One bit per position and direction on the map, for spotting when the guard repeats itself.
+struct TurnBitmap {
+    bits: Vec<u64>,
+    min_x: i64,
+    min_y: i64,
+    width: i64,
+}
+
+impl TurnBitmap {
+    fn new(map: &Map) -> Self {
+        let width = map.max_x - map.min_x + 1;
+        let height = map.max_y - map.min_y + 1;
+        let len = (width * height * 4) as usize;
+        Self {
+            bits: vec![0; len.div_ceil(64)],
+            min_x: map.min_x,
+            min_y: map.min_y,
+            width,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    /// Mark the guard's state as seen, returning false if it already was.
+    fn insert(&mut self, guard: &Guard) -> bool {
+        let cell = (guard.position.y - self.min_y) * self.width + (guard.position.x - self.min_x);
+        let idx = cell as usize * 4 + guard.direction.index();
+        let (word, bit) = (idx / 64, 1 << (idx % 64));
+        let new = self.bits[word] & bit == 0;
+        self.bits[word] |= bit;
+        new
+    }
+}
+
+/// Whether the guard ends up walking in a loop, reusing `turns` between calls.
+fn loops(mut guard: Guard, map: &Map, turns: &mut TurnBitmap) -> bool {
+    turns.clear();
+    while let Some(stop) = map.next_stop(&guard) {
+        guard.position = stop;
+        guard.turn();
+        if !turns.insert(&guard) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Follow the guard by jumping straight to the next obstacle, rather than stepping cell by cell.
+fn simulate(mut guard: Guard, map: &Map) -> Route {
+    let mut segments = Vec::new();
+    let mut turns = TurnBitmap::new(map);
+
+    loop {
+        let Some(stop) = map.next_stop(&guard) else {
+            segments.push(Segment {
+                start: guard.position,
+                end: map.edge(&guard),
+                direction: guard.direction,
+            });
+            return Route {
+                segments,
+                looped: false,
+            };
+        };
+
+        segments.push(Segment {
+            start: guard.position,
+            end: stop,
+            direction: guard.direction,
+        });
+        guard.position = stop;
+        guard.turn();
+
+        if !turns.insert(&guard) {
+            return Route {
+                segments,
+                looped: true,
+            };
+        }
+    }
+}
+
+/// Step by step replay of a guard's walk, starting with its initial state. Each step is either a
+/// move forward or a turn. Ends when the guard leaves the map or repeats an earlier state.
+struct Replay<'a> {
+    map: &'a Map,
+    next: Option<Guard>,
+    seen: TurnBitmap,
+    repeated: Option<Guard>,
+}
+
+impl<'a> Replay<'a> {
+    fn new(guard: Guard, map: &'a Map) -> Self {
+        Self {
+            map,
+            next: Some(guard),
+            seen: TurnBitmap::new(map),
+            repeated: None,
+        }
+    }
+
+    /// Once the replay has ended, the state the guard came back to if it was stuck in a loop.
+    fn repeated(&self) -> Option<Guard> {
+        self.repeated
+    }
+}
+
+impl Iterator for Replay<'_> {
+    type Item = Guard;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        if !self.seen.insert(&current) {
+            self.repeated = Some(current);
+            return None;
+        }
+
+        let mut next = current;
+        let ahead = current.position.add_dir(current.direction);
+        if self.map.obstacles.contains(&ahead) {
+            next.turn();
+        } else {
+            next.position = ahead;
+        }
+        self.next = self.map.in_map(&next.position).then_some(next);
+
+        Some(current)
+    }
+}
+
+/// Draw the map with the guards' routes marked as in the puzzle, `|` and `-` for straight moves
+/// and `+` where a guard turns or crosses its path. Guards are drawn at their starting positions
+/// and any `obstructions` as `O`.
+fn render(guards: &[Guard], map: &Map, obstructions: &[Position]) -> String {
+    fn mark(marks: &mut HashMap<Position, char>, position: Position, symbol: char) {
+        let existing = marks.entry(position).or_insert(symbol);
+        if *existing != symbol {
+            *existing = '+';
+        }
+    }
+
+    let mut marks = HashMap::new();
+    for guard in guards {
+        let steps: Vec<Guard> = Replay::new(*guard, map).collect();
+        for (from, to) in steps.iter().zip(steps.iter().skip(1)) {
+            if from.direction != to.direction {
+                marks.insert(from.position, '+');
+            } else {
+                let symbol = match from.direction {
+                    Direction::North | Direction::South => '|',
+                    Direction::East | Direction::West => '-',
+                };
+                mark(&mut marks, from.position, symbol);
+                mark(&mut marks, to.position, symbol);
+            }
+        }
+    }
+    for guard in guards {
+        marks.insert(guard.position, guard.direction.symbol());
+    }
+    for obstruction in obstructions {
+        marks.insert(*obstruction, 'O');
+    }
+
+    let mut output = String::new();
+    for y in map.min_y..=map.max_y {
+        for x in map.min_x..=map.max_x {
+            let position = Position { x, y };
+            match marks.get(&position) {
+                Some('O') => output.push('O'),
+                _ if map.obstacles.contains(&position) => output.push('#'),
+                Some(mark) => output.push(*mark),
+                None => output.push('.'),
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn parse_input(input: &str) -> (Vec<Guard>, Map) {
+    let mut guards = Vec::new();
+    let mut map = Map {
+        min_x: 0,
+        max_x: 0,
+        min_y: 0,
+        max_y: 0,
+        obstacles: HashSet::new(),
+        by_x: HashMap::new(),
+        by_y: HashMap::new(),
+    };
 
-    let mut max_x = 0;
-    let mut max_y = 0;
     for (y, row) in input.lines().enumerate() {
-        max_y = y as i64;
-        max_x = row.chars().count() as i64 - 1;
+        map.max_y = y as i64;
+        map.max_x = row.chars().count() as i64 - 1;
         for (x, col) in row.chars().enumerate() {
             match col {
                 '.' => (),
-                '^' => {
-                    guard = Some(Guard {
-                        position: Position {
-                            x: x as i64,
-                            y: y as i64,
-                        },
-                        direction: Direction::North,
-                    })
-                }
+                '^' | '>' | 'v' | '<' => guards.push(Guard {
+                    position: Position {
+                        x: x as i64,
+                        y: y as i64,
+                    },
+                    direction: Direction::from_symbol(col).unwrap(),
+                    turn_policy: TurnPolicy::Right,
+                }),
                 '#' => {
-                    obstacles.insert(Position {
+                    map.add_obstacle(Position {
                         x: x as i64,
                         y: y as i64,
                     });
@@ -110,79 +449,147 @@ fn parse_input(input: &str) -> (Guard, Map) {
         }
     }
 
-    let Some(guard) = guard else {
+    if guards.is_empty() {
         panic!("Didn't find guard");
-    };
+    }
 
-    let mut by_x: HashMap<i64, HashSet<Position>> = HashMap::new();
-    let mut by_y: HashMap<i64, HashSet<Position>> = HashMap::new();
+    (guards, map)
+}
 
-    for obs in obstacles.iter() {
-        by_x.entry(obs.x).or_default().insert(*obs);
-        by_y.entry(obs.y).or_default().insert(*obs);
-    }
+/// Cells visited by any of the guards. Guards don't get in each other's way.
+fn solve1(guards: &[Guard], map: &Map) -> usize {
+    let visited: HashSet<Position> = guards
+        .iter()
+        .flat_map(|guard| simulate(*guard, map).segments)
+        .flat_map(|s| s.positions().collect::<Vec<_>>())
+        .collect();
 
-    (
-        guard,
-        Map {
-            min_x: 0,
-            max_x,
-            min_y: 0,
-            max_y,
-            obstacles,
-        },
-    )
+    visited.len()
 }
 
-fn solve1(mut guard: Guard, map: &Map) -> usize {
-    let mut visited = HashSet::new();
-    visited.insert(guard.position);
+/// Each cell of the guard's route paired with the state just before the guard first walks onto
+/// it, excluding the starting cell.
+fn first_approaches(guard: Guard, map: &Map) -> Vec<(Position, Guard)> {
+    let mut seen = HashSet::from([guard.position]);
+    let mut approaches = Vec::new();
+    let mut previous = guard.position;
 
-    while map.in_map(&guard.position) {
-        guard.do_move(&map.obstacles);
-        visited.insert(guard.position);
+    for segment in simulate(guard, map).segments {
+        for position in segment.positions().skip(1) {
+            if seen.insert(position) {
+                approaches.push((
+                    position,
+                    Guard {
+                        position: previous,
+                        direction: segment.direction,
+                        ..guard
+                    },
+                ));
+            }
+            previous = position;
+        }
     }
 
-    visited.len() - 1
+    approaches
 }
 
-fn solve2(guard: &Guard, mut map: Map) -> usize {
-    let mut total = 0;
-
-    for x in map.min_x..=map.max_x {
-        for y in map.min_y..=map.max_y {
-            let candidate_obstacle_position = Position { x, y };
-            if candidate_obstacle_position != guard.position
-                && !map.obstacles.contains(&candidate_obstacle_position)
-            {
-                map.obstacles.insert(candidate_obstacle_position);
-                let mut guard_copy = *guard;
-
-                let mut visited = HashSet::new();
-                visited.insert(guard_copy);
-
-                while map.in_map(&guard_copy.position) {
-                    guard_copy.do_move(&map.obstacles);
-                    if visited.contains(&guard_copy) {
-                        total += 1;
-                        break;
-                    }
-                    visited.insert(guard_copy);
-                }
+/// Positions where a single new obstacle leaves the guard walking in a loop.
+fn loop_obstruction_positions(guard: &Guard, map: &mut Map) -> Vec<Position> {
+    let mut positions = Vec::new();
+    let mut turns = TurnBitmap::new(map);
 
-                map.obstacles.remove(&candidate_obstacle_position);
-            }
+    // An obstacle off the original route is never hit, and everything before the guard first
+    // reaches it plays out the same, so only resume from there
+    for (candidate_obstacle_position, approach) in first_approaches(*guard, map) {
+        map.add_obstacle(candidate_obstacle_position);
+        if loops(approach, map, &mut turns) {
+            positions.push(candidate_obstacle_position);
         }
+        map.remove_obstacle(candidate_obstacle_position);
     }
 
-    total
+    positions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LoopObstruction {
+    position: Position,
+    /// First state of the guard which is part of the loop
+    entry: Guard,
+    /// Replay steps, moves and turns, for one lap of the loop
+    cycle_length: usize,
+}
+
+fn find_loop_obstructions(guard: &Guard, mut map: Map) -> Vec<LoopObstruction> {
+    loop_obstruction_positions(guard, &mut map)
+        .into_iter()
+        .map(|position| {
+            map.add_obstacle(position);
+            let mut replay = Replay::new(*guard, &map);
+            let steps: Vec<Guard> = replay.by_ref().collect();
+            let entry = replay.repeated().expect("Guard should be looping");
+            map.remove_obstacle(position);
+
+            let entry_idx = steps.iter().position(|s| *s == entry).unwrap();
+            LoopObstruction {
+                position,
+                entry,
+                cycle_length: steps.len() - entry_idx,
+            }
+        })
+        .collect()
+}
+
+fn solve2(guard: &Guard, mut map: Map) -> usize {
+    loop_obstruction_positions(guard, &mut map).len()
 }
 
 fn main() {
-    let (guard, map) = parse_input(include_str!("input.txt"));
+    let (mut guards, map) = parse_input(include_str!("input.txt"));
 
-    println!("Part 1: {}", solve1(guard, &map));
-    println!("Part 2: {}", solve2(&guard, map));
+    // `--turn <right|left|reverse>` changes what the guards do at an obstacle
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--turn") {
+        let policy: TurnPolicy = args
+            .get(idx + 1)
+            .expect("Missing turn policy")
+            .parse()
+            .unwrap();
+        for guard in guards.iter_mut() {
+            guard.turn_policy = policy;
+        }
+    }
+
+    println!("Part 1: {}", solve1(&guards, &map));
+    // Each guard has their own obstruction positions, so only a lone guard gets a single answer
+    if let [guard] = guards.as_slice() {
+        println!("Part 2: {}", solve2(guard, map.clone()));
+    } else {
+        for (idx, guard) in guards.iter().enumerate() {
+            println!("Guard {idx} loops: {}", solve2(guard, map.clone()));
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--obstructions") {
+        for guard in &guards {
+            let found = find_loop_obstructions(guard, map.clone());
+            for obstruction in &found {
+                println!(
+                    "Obstruction at ({}, {}): loop of {} steps entered at ({}, {}) facing {:?}",
+                    obstruction.position.x,
+                    obstruction.position.y,
+                    obstruction.cycle_length,
+                    obstruction.entry.position.x,
+                    obstruction.entry.position.y,
+                    obstruction.entry.direction
+                );
+            }
+            let positions: Vec<Position> = found.iter().map(|o| o.position).collect();
+            print!("{}", render(&[*guard], &map, &positions));
+        }
+    } else if args.iter().any(|arg| arg == "--render") {
+        print!("{}", render(&guards, &map, &[]));
+    }
 }
 
 #[cfg(test)]
@@ -193,25 +600,172 @@ mod tests {
 
     #[test]
     fn parse_test() {
-        let (guard, _obstacles) = parse_input(INPUT);
+        let (guards, _obstacles) = parse_input(INPUT);
         assert_eq!(
-            guard,
-            Guard {
+            guards,
+            &[Guard {
                 position: Position { x: 4, y: 6 },
-                direction: Direction::North
-            }
+                direction: Direction::North,
+                turn_policy: TurnPolicy::Right,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simulate() {
+        let (guards, map) = parse_input(INPUT);
+        let guard = guards[0];
+        let route = simulate(guard, &map);
+
+        assert!(!route.looped);
+        assert_eq!(
+            route.segments[..2],
+            [
+                Segment {
+                    start: Position { x: 4, y: 6 },
+                    end: Position { x: 4, y: 1 },
+                    direction: Direction::North
+                },
+                Segment {
+                    start: Position { x: 4, y: 1 },
+                    end: Position { x: 8, y: 1 },
+                    direction: Direction::East
+                },
+            ]
+        );
+        assert_eq!(
+            route.segments.last(),
+            Some(&Segment {
+                start: Position { x: 7, y: 7 },
+                end: Position { x: 7, y: 9 },
+                direction: Direction::South
+            })
         );
     }
 
     #[test]
     fn test_solve1() {
-        let (guard, map) = parse_input(INPUT);
-        assert_eq!(solve1(guard, &map), 41);
+        let (guards, map) = parse_input(INPUT);
+        assert_eq!(solve1(&guards, &map), 41);
     }
 
     #[test]
     fn test_solve2() {
-        let (guard, map) = parse_input(INPUT);
+        let (guards, map) = parse_input(INPUT);
+        let guard = guards[0];
         assert_eq!(solve2(&guard, map), 6);
     }
+
+    #[test]
+    fn test_first_approaches() {
+        let (guards, map) = parse_input(INPUT);
+        let guard = guards[0];
+        let approaches = first_approaches(guard, &map);
+
+        assert_eq!(approaches.len(), 40);
+        assert_eq!(
+            approaches[0],
+            (
+                Position { x: 4, y: 5 },
+                Guard {
+                    position: Position { x: 4, y: 6 },
+                    direction: Direction::North,
+                    turn_policy: TurnPolicy::Right,
+                }
+            )
+        );
+        // The guard is standing on its start cell, so it can never be a candidate
+        assert!(approaches.iter().all(|(p, _)| *p != guard.position));
+    }
+
+    #[test]
+    fn test_multiple_guards() {
+        let (guards, map) = parse_input("....#\n>...<\n.....\n..^..");
+        assert_eq!(
+            guards.iter().map(|g| g.direction).collect::<Vec<_>>(),
+            &[Direction::East, Direction::West, Direction::North]
+        );
+
+        // East and west guards both cover the second row, the north guard crosses it once
+        assert_eq!(solve1(&guards[..2], &map), 5);
+        assert_eq!(solve1(&guards[2..], &map), 4);
+        assert_eq!(solve1(&guards, &map), 8);
+    }
+
+    #[test]
+    fn test_turn_policy() {
+        let (guards, map) = parse_input(".#.\n...\n#^#\n.#.");
+        let mut guard = guards[0];
+
+        guard.turn_policy = TurnPolicy::Reverse;
+        let route = simulate(guard, &map);
+        assert!(route.looped);
+        assert_eq!(route.segments.len(), 3);
+
+        guard.turn_policy = TurnPolicy::Left;
+        let steps: Vec<Position> = Replay::new(guard, &map).map(|g| g.position).collect();
+        assert_eq!(
+            steps,
+            &[
+                Position { x: 1, y: 2 },
+                Position { x: 1, y: 1 },
+                Position { x: 1, y: 1 },
+                Position { x: 0, y: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let (guards, mut map) = parse_input(INPUT);
+        map.add_obstacle(Position { x: 3, y: 6 });
+
+        assert_eq!(
+            render(&guards, &map, &[]),
+            "....#.....\n\
+             ....+---+#\n\
+             ....|...|.\n\
+             ..#.|...|.\n\
+             ....|..#|.\n\
+             ....|...|.\n\
+             .#.#^---+.\n\
+             ........#.\n\
+             #.........\n\
+             ......#...\n"
+        );
+    }
+
+    #[test]
+    fn test_find_loop_obstructions() {
+        let (guards, mut map) = parse_input(INPUT);
+        let found = find_loop_obstructions(&guards[0], map.clone());
+
+        let mut positions: Vec<(i64, i64)> =
+            found.iter().map(|o| (o.position.x, o.position.y)).collect();
+        positions.sort();
+        assert_eq!(positions, &[(1, 8), (3, 6), (3, 8), (6, 7), (7, 7), (7, 9)]);
+
+        // The first loop from the puzzle goes straight back through the guard's start
+        let first = found
+            .iter()
+            .find(|o| o.position == Position { x: 3, y: 6 })
+            .unwrap();
+        assert_eq!(first.entry, guards[0]);
+        assert_eq!(first.cycle_length, 22);
+
+        map.add_obstacle(first.position);
+        assert_eq!(
+            render(&guards, &map, &[first.position]),
+            "....#.....\n\
+             ....+---+#\n\
+             ....|...|.\n\
+             ..#.|...|.\n\
+             ....|..#|.\n\
+             ....|...|.\n\
+             .#.O^---+.\n\
+             ........#.\n\
+             #.........\n\
+             ......#...\n"
+        );
+    }
 }
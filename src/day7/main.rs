@@ -1,93 +1,397 @@
 use nom::bytes::complete::tag;
-use nom::character::complete::{i64 as parse_i64, newline, space1};
+use nom::character::complete::{i128 as parse_i128, newline, space1};
 use nom::multi::separated_list1;
 use nom::{sequence::separated_pair, IResult};
 
-fn parse_input(input: &str) -> Vec<(i64, Vec<i64>)> {
+use operator::{Add, Concat, Mul, Operator};
+
+fn parse_input(input: &str) -> Vec<(i128, Vec<i128>)> {
     let parsed = separated_list1(newline, parse_line)(input).unwrap();
     assert!(parsed.0.is_empty());
     parsed.1
 }
 
-fn parse_line(input: &str) -> IResult<&str, (i64, Vec<i64>)> {
-    separated_pair(parse_i64, tag(": "), separated_list1(space1, parse_i64))(input)
+fn parse_line(input: &str) -> IResult<&str, (i128, Vec<i128>)> {
+    separated_pair(parse_i128, tag(": "), separated_list1(space1, parse_i128))(input)
 }
 
-mod part1 {
-    fn is_solvable(result: i64, rem_nums: &[i64], value: i64) -> bool {
-        if rem_nums.is_empty() {
-            value == result
-        } else {
-            let next_val = rem_nums[0];
-            // add
-            let new_val = value + next_val;
-            if is_solvable(result, &rem_nums[1..], new_val) {
-                return true;
+mod operator {
+    use std::fmt::Debug;
+    use std::ops::RangeInclusive;
+
+    /// A binary operator usable in an equation, evaluated left to right. All arithmetic is
+    /// checked, anything which would overflow is treated as not defined.
+    pub trait Operator: Debug + Sync {
+        fn symbol(&self) -> &'static str;
+
+        /// `left op right`, or `None` if it isn't defined for these operands.
+        fn apply(&self, left: i128, right: i128) -> Option<i128>;
+
+        /// Every `left` for which `left op right == target`, or `None` if there are none.
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>>;
+
+        /// Cheap check whether `target` could be the result of `_ op right` at all.
+        fn feasible(&self, target: i128, right: i128) -> bool {
+            self.invert(target, right).is_some()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Add;
+
+    impl Operator for Add {
+        fn symbol(&self) -> &'static str {
+            "+"
+        }
+
+        fn apply(&self, left: i128, right: i128) -> Option<i128> {
+            left.checked_add(right)
+        }
+
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>> {
+            let left = target.checked_sub(right)?;
+            Some(left..=left)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Sub;
+
+    impl Operator for Sub {
+        fn symbol(&self) -> &'static str {
+            "-"
+        }
+
+        fn apply(&self, left: i128, right: i128) -> Option<i128> {
+            left.checked_sub(right)
+        }
+
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>> {
+            let left = target.checked_add(right)?;
+            Some(left..=left)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Mul;
+
+    impl Operator for Mul {
+        fn symbol(&self) -> &'static str {
+            "*"
+        }
+
+        fn apply(&self, left: i128, right: i128) -> Option<i128> {
+            left.checked_mul(right)
+        }
+
+        fn feasible(&self, target: i128, right: i128) -> bool {
+            if right == 0 {
+                target == 0
+            } else {
+                target.checked_rem(right) == Some(0)
             }
+        }
 
-            // mul
-            let new_val = value * next_val;
-            is_solvable(result, &rem_nums[1..], new_val)
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>> {
+            if !self.feasible(target, right) {
+                return None;
+            }
+            // Anything times zero is zero
+            if right == 0 {
+                return Some(i128::MIN..=i128::MAX);
+            }
+            let left = target.checked_div(right)?;
+            Some(left..=left)
         }
     }
 
-    pub fn solve1(input: &[(i64, Vec<i64>)]) -> i64 {
-        let mut total = 0;
-        for (result, nums) in input {
-            if is_solvable(*result, &nums[1..], nums[0]) {
-                total += *result;
+    /// Integer division, truncating towards zero.
+    #[derive(Debug)]
+    pub struct Div;
+
+    impl Operator for Div {
+        fn symbol(&self) -> &'static str {
+            "/"
+        }
+
+        fn apply(&self, left: i128, right: i128) -> Option<i128> {
+            left.checked_div(right)
+        }
+
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>> {
+            // Truncation is symmetric, so `left / -r == t` exactly when `left / r == -t`
+            let (target, right) = match right {
+                0 => return None,
+                r if r < 0 => (target.checked_neg()?, right.checked_neg()?),
+                _ => (target, right),
+            };
+            let base = target.checked_mul(right)?;
+            let spread = right - 1;
+            Some(match target {
+                // Clamp rather than give up, part of the range may still be representable
+                t if t > 0 => base..=base.saturating_add(spread),
+                t if t < 0 => base.saturating_sub(spread)..=base,
+                _ => -spread..=spread,
+            })
+        }
+    }
+
+    /// Joins the decimal digits of both operands, as if written next to each other:
+    /// `12 || 345 == 12345` and `-12 || 3 == -123`. A negative `right` can't be joined.
+    #[derive(Debug)]
+    pub struct Concat;
+
+    impl Concat {
+        fn shift(right: i128) -> Option<i128> {
+            10_i128.checked_pow(right.checked_ilog10().unwrap_or(0) + 1)
+        }
+    }
+
+    impl Operator for Concat {
+        fn symbol(&self) -> &'static str {
+            "||"
+        }
+
+        fn apply(&self, left: i128, right: i128) -> Option<i128> {
+            if right < 0 {
+                return None;
             }
+            let shifted = left.checked_mul(Self::shift(right)?)?;
+            if left < 0 {
+                shifted.checked_sub(right)
+            } else {
+                shifted.checked_add(right)
+            }
+        }
+
+        fn invert(&self, target: i128, right: i128) -> Option<RangeInclusive<i128>> {
+            if right < 0 {
+                return None;
+            }
+            let shift = Self::shift(right)?;
+            let left = if target >= 0 {
+                let rest = target.checked_sub(right).filter(|rest| *rest >= 0)?;
+                (rest % shift == 0).then_some(rest / shift)?
+            } else {
+                // Only a negative `left` gives a negative result, so it can't be zero
+                let rest = target
+                    .checked_neg()?
+                    .checked_sub(right)
+                    .filter(|rest| *rest > 0)?;
+                (rest % shift == 0).then_some(-(rest / shift))?
+            };
+            Some(left..=left)
         }
+    }
+
+    pub const ALL: &[&dyn Operator] = &[&Add, &Sub, &Mul, &Div, &Concat];
 
-        total
+    pub fn from_symbol(symbol: &str) -> Option<&'static dyn Operator> {
+        ALL.iter().find(|op| op.symbol() == symbol).copied()
     }
 }
 
-mod part2 {
-    fn is_solvable(result: i64, rem_nums: &[i64], value: i64) -> bool {
-        if rem_nums.is_empty() {
-            value == result
-        } else {
-            let next_val = rem_nums[0];
-            // add
-            let new_val = value + next_val;
-            if is_solvable(result, &rem_nums[1..], new_val) {
-                return true;
+mod solver {
+    use std::fmt;
+
+    use super::Operator;
+
+    /// Operators placed between the numbers of an equation, evaluated left to right.
+    #[derive(Debug, Clone)]
+    pub struct Assignment {
+        pub nums: Vec<i128>,
+        pub ops: Vec<&'static dyn Operator>,
+    }
+
+    impl Assignment {
+        pub fn evaluate(&self) -> Option<i128> {
+            self.ops
+                .iter()
+                .zip(&self.nums[1..])
+                .try_fold(self.nums[0], |left, (op, right)| op.apply(left, *right))
+        }
+    }
+
+    impl fmt::Display for Assignment {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.nums[0])?;
+            for (op, num) in self.ops.iter().zip(&self.nums[1..]) {
+                write!(f, " {} {num}", op.symbol())?;
             }
+            Ok(())
+        }
+    }
 
-            // mul
-            let new_val = value * next_val;
-            if is_solvable(result, &rem_nums[1..], new_val) {
-                return true;
+    /// Work backwards from `target`, undoing the last number with each operator in turn. Only
+    /// operators which could have produced the target are followed, which prunes most of the
+    /// tree. Calls `visit` with the operators (last first) of every assignment found, stopping
+    /// early once it returns false.
+    fn search(
+        target: i128,
+        nums: &[i128],
+        ops: &[&'static dyn Operator],
+        chosen: &mut Vec<&'static dyn Operator>,
+        visit: &mut dyn FnMut(&[&'static dyn Operator]) -> bool,
+    ) -> bool {
+        let (last, rest) = nums.split_last().expect("Equation has no numbers");
+        if rest.is_empty() {
+            return target != *last || visit(chosen);
+        }
+
+        for op in ops {
+            if !op.feasible(target, *last) {
+                continue;
             }
+            let Some(lefts) = op.invert(target, *last) else {
+                continue;
+            };
 
-            // concat
-            let new_val = (value.to_string() + &next_val.to_string()).parse().unwrap();
-            is_solvable(result, &rem_nums[1..], new_val)
+            chosen.push(*op);
+            let keep_going = if lefts.start() == lefts.end() {
+                search(*lefts.start(), rest, ops, chosen, visit)
+            } else {
+                // Too many candidates to undo one at a time, so try every way of evaluating
+                // `rest` and keep those that land in range
+                let mut path = Vec::new();
+                evaluate_all(rest[0], &rest[1..], ops, &mut path, &mut |value, path| {
+                    if !lefts.contains(&value) {
+                        return true;
+                    }
+                    let full: Vec<_> = chosen.iter().chain(path.iter().rev()).copied().collect();
+                    visit(&full)
+                })
+            };
+            chosen.pop();
+            if !keep_going {
+                return false;
+            }
         }
+
+        true
     }
 
-    pub fn solve2(input: &[(i64, Vec<i64>)]) -> i64 {
-        let mut total = 0;
-        for (result, nums) in input {
-            if is_solvable(*result, &nums[1..], nums[0]) {
-                total += *result;
+    /// Evaluate `left` followed by `nums` forwards with every combination of `ops`, calling
+    /// `visit` with each value and the operators (first first) that gave it. Stops early once
+    /// `visit` returns false.
+    fn evaluate_all(
+        left: i128,
+        nums: &[i128],
+        ops: &[&'static dyn Operator],
+        path: &mut Vec<&'static dyn Operator>,
+        visit: &mut dyn FnMut(i128, &[&'static dyn Operator]) -> bool,
+    ) -> bool {
+        let Some((right, rest)) = nums.split_first() else {
+            return visit(left, path);
+        };
+
+        for op in ops {
+            let Some(value) = op.apply(left, *right) else {
+                continue;
+            };
+            path.push(*op);
+            let keep_going = evaluate_all(value, rest, ops, path, visit);
+            path.pop();
+            if !keep_going {
+                return false;
             }
         }
 
-        total
+        true
+    }
+
+    pub fn is_solvable(result: i128, nums: &[i128], ops: &[&'static dyn Operator]) -> bool {
+        !search(result, nums, ops, &mut Vec::new(), &mut |_| false)
     }
+
+    /// Up to `limit` assignments of `ops` which make the equation true.
+    pub fn list_assignments(
+        result: i128,
+        nums: &[i128],
+        ops: &[&'static dyn Operator],
+        limit: usize,
+    ) -> Vec<Assignment> {
+        let mut found = Vec::new();
+        if limit > 0 {
+            search(result, nums, ops, &mut Vec::new(), &mut |chosen| {
+                let assignment = Assignment {
+                    nums: nums.into(),
+                    ops: chosen.iter().rev().copied().collect(),
+                };
+                debug_assert_eq!(assignment.evaluate(), Some(result));
+                found.push(assignment);
+                found.len() < limit
+            });
+        }
+        found
+    }
+
+    pub fn find_assignment(
+        result: i128,
+        nums: &[i128],
+        ops: &[&'static dyn Operator],
+    ) -> Option<Assignment> {
+        list_assignments(result, nums, ops, 1).pop()
+    }
+
+    pub fn count_assignments(result: i128, nums: &[i128], ops: &[&'static dyn Operator]) -> usize {
+        let mut count = 0;
+        search(result, nums, ops, &mut Vec::new(), &mut |_| {
+            count += 1;
+            true
+        });
+        count
+    }
+}
+
+const PART1_OPS: &[&dyn Operator] = &[&Add, &Mul];
+const PART2_OPS: &[&dyn Operator] = &[&Add, &Mul, &Concat];
+
+fn solve(input: &[(i128, Vec<i128>)], ops: &[&'static dyn Operator]) -> i128 {
+    input
+        .iter()
+        .filter(|(result, nums)| solver::is_solvable(*result, nums, ops))
+        .map(|(result, _)| result)
+        .sum()
 }
 
 fn main() {
     let parsed = parse_input(include_str!("input.txt"));
 
-    println!("Part 1: {}", part1::solve1(&parsed));
-    println!("Part 2: {}", part2::solve2(&parsed));
+    println!("Part 1: {}", solve(&parsed, PART1_OPS));
+    println!("Part 2: {}", solve(&parsed, PART2_OPS));
+
+    // `--ops "+ - * / ||"` solves with any other set of operators
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--ops") {
+        let ops: Vec<&dyn Operator> = args
+            .get(idx + 1)
+            .expect("Missing operators")
+            .split_whitespace()
+            .map(|symbol| operator::from_symbol(symbol).expect("Unknown operator"))
+            .collect();
+        println!("Custom: {}", solve(&parsed, &ops));
+    }
+
+    if args.iter().any(|arg| arg == "--explain") {
+        for (result, nums) in &parsed {
+            // Prefer the part 1 operators, only reaching for concat when needed
+            let found = [PART1_OPS, PART2_OPS]
+                .into_iter()
+                .find_map(|ops| Some((ops, solver::find_assignment(*result, nums, ops)?)));
+            match found {
+                Some((ops, assignment)) => println!(
+                    "{result} = {assignment} ({} ways)",
+                    solver::count_assignments(*result, nums, ops)
+                ),
+                None => println!("{result}: unsolvable"),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::operator::Div;
     use super::*;
 
     const INPUT: &str = include_str!("test.txt");
@@ -104,12 +408,169 @@ mod tests {
     #[test]
     fn test1() {
         let parsed = parse_input(INPUT);
-        assert_eq!(part1::solve1(&parsed), 3749);
+        assert_eq!(solve(&parsed, PART1_OPS), 3749);
     }
 
     #[test]
     fn test2() {
         let parsed = parse_input(INPUT);
-        assert_eq!(part2::solve2(&parsed), 11387);
+        assert_eq!(solve(&parsed, PART2_OPS), 11387);
+    }
+
+    #[test]
+    fn test_find_assignment() {
+        let parsed = parse_input(INPUT);
+
+        let assignment = solver::find_assignment(parsed[0].0, &parsed[0].1, PART1_OPS);
+        assert_eq!(assignment.unwrap().to_string(), "10 * 19");
+        assert!(solver::find_assignment(parsed[2].0, &parsed[2].1, PART1_OPS).is_none());
+
+        let assignment = solver::find_assignment(parsed[4].0, &parsed[4].1, PART2_OPS);
+        assert_eq!(assignment.unwrap().to_string(), "6 * 8 || 6 * 15");
+    }
+
+    #[test]
+    fn test_list_assignments() {
+        let parsed = parse_input(INPUT);
+        let (result, nums) = &parsed[1];
+
+        assert_eq!(solver::count_assignments(*result, nums, PART1_OPS), 2);
+        let mut all: Vec<String> = solver::list_assignments(*result, nums, PART1_OPS, 10)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        all.sort();
+        assert_eq!(all, &["81 * 40 + 27", "81 + 40 * 27"]);
+        assert_eq!(
+            solver::list_assignments(*result, nums, PART1_OPS, 1).len(),
+            1
+        );
+
+        let solvable: i128 = parsed
+            .iter()
+            .filter(|(result, nums)| solver::count_assignments(*result, nums, PART2_OPS) > 0)
+            .map(|(result, _)| result)
+            .sum();
+        assert_eq!(solvable, 11387);
+    }
+
+    #[test]
+    fn test_operator_inverse() {
+        for op in operator::ALL {
+            for left in -30..=30 {
+                for right in -7..=7 {
+                    if let Some(target) = op.apply(left, right) {
+                        let inverse = op.invert(target, right);
+                        assert!(
+                            inverse.is_some_and(|r| r.contains(&left)),
+                            "{left} {} {right} = {target}",
+                            op.symbol()
+                        );
+                    }
+                }
+            }
+        }
+
+        assert_eq!(Div.invert(3, 4), Some(12..=15));
+        assert_eq!(Div.invert(-3, 4), Some(-15..=-12));
+        assert_eq!(Concat.invert(12345, 345), Some(12..=12));
+        assert_eq!(Concat.invert(12345, 45), Some(123..=123));
+        assert_eq!(Concat.invert(12345, 44), None);
+        assert_eq!(Mul.invert(12, 5), None);
+        assert_eq!(Mul.invert(0, 0), Some(i128::MIN..=i128::MAX));
+        assert_eq!(Mul.invert(3, 0), None);
+    }
+
+    #[test]
+    fn test_multiply_by_zero() {
+        assert!(solver::is_solvable(0, &[5, 0], PART1_OPS));
+        assert!(solver::is_solvable(0, &[5, 3, 0], PART1_OPS));
+        assert!(!solver::is_solvable(1, &[5, 0], PART1_OPS));
+        // 5 + 3 * 0 and 5 * 3 * 0
+        assert_eq!(solver::count_assignments(0, &[5, 3, 0], PART1_OPS), 2);
+        let assignment = solver::find_assignment(0, &[5, 3, 0], PART1_OPS).unwrap();
+        assert_eq!(assignment.evaluate(), Some(0));
+    }
+
+    #[test]
+    fn test_large_divisor() {
+        assert!(solver::is_solvable(0, &[5, 1_000_000_000_000], &[&Div]));
+        assert!(solver::is_solvable(
+            0,
+            &[5, 7, 1_000_000_000_000],
+            &[&Add, &Mul, &Div]
+        ));
+        assert!(!solver::is_solvable(1, &[5, 1_000_000_000_000], &[&Div]));
+        assert_eq!(
+            solver::find_assignment(3, &[3_000_000_000_000, 7, 1_000_000_000_000], &[&Add, &Div])
+                .unwrap()
+                .to_string(),
+            "3000000000000 + 7 / 1000000000000"
+        );
+    }
+
+    #[test]
+    fn test_custom_operators() {
+        let ops = &[
+            operator::from_symbol("-").unwrap(),
+            operator::from_symbol("/").unwrap(),
+        ];
+        // (20 - 2) / 3 - 1
+        let assignment = solver::find_assignment(5, &[20, 2, 3, 1], ops).unwrap();
+        assert_eq!(assignment.to_string(), "20 - 2 / 3 - 1");
+        // 7 / 2 truncates to 3
+        assert!(solver::is_solvable(3, &[7, 2], ops));
+        assert!(!solver::is_solvable(4, &[7, 2], ops));
+    }
+
+    #[test]
+    fn test_concat_negative() {
+        assert_eq!(Concat.apply(-12, 3), Some(-123));
+        assert_eq!(Concat.apply(12, -3), None);
+        assert_eq!(Concat.apply(0, 5), Some(5));
+        assert_eq!(Concat.invert(-123, 3), Some(-12..=-12));
+        assert_eq!(Concat.invert(-3, 3), None);
+        assert_eq!(Concat.invert(123, -3), None);
+
+        let ops = &[operator::from_symbol("-").unwrap(), &Concat];
+        assert_eq!(
+            solver::find_assignment(-17, &[1, 2, 7], ops)
+                .unwrap()
+                .to_string(),
+            "1 - 2 || 7"
+        );
+    }
+
+    #[test]
+    fn test_near_limits() {
+        let max = i64::MAX as i128;
+
+        // Overflows i64 half way through, fine in i128
+        assert!(solver::is_solvable(max, &[max, 2, 2], &[&Mul, &Div]));
+        assert!(solver::is_solvable(max, &[max - 1, 1], PART1_OPS));
+        assert!(!solver::is_solvable(max, &[max, 2], PART1_OPS));
+
+        // i64::MAX has 19 digits, so concatenating it needs a shift of 10^19
+        let joined = solver::find_assignment(19223372036854775807, &[1, max], PART2_OPS);
+        assert_eq!(joined.unwrap().to_string(), "1 || 9223372036854775807");
+
+        // Branches which overflow even i128 are pruned rather than panicking
+        assert!(!solver::is_solvable(1, &[max, max, max], PART2_OPS));
+        assert!(!solver::is_solvable(
+            i128::MAX,
+            &[i128::MAX, 2, 1],
+            PART2_OPS
+        ));
+        assert!(solver::is_solvable(i128::MAX, &[i128::MAX, 1, 1], &[&Mul]));
+        assert!(solver::is_solvable(
+            i128::MIN,
+            &[i128::MIN, 1],
+            &[&Mul, &Concat]
+        ));
+        assert_eq!(Add.invert(i128::MIN, 1), None);
+        assert_eq!(Mul.apply(i128::MAX, 2), None);
+        assert_eq!(Concat.apply(i128::MAX / 10, 99), None);
+        assert_eq!(Div.invert(i128::MAX, 2), None);
+        assert_eq!(Div.invert(i128::MIN, -1), None);
     }
 }
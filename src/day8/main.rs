@@ -1,143 +1,319 @@
 use std::collections::{HashMap, HashSet};
 
+/// A point on a map with `N` dimensions, `[x, y]` for the puzzle's 2D maps.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Point {
-    x: i64,
-    y: i64,
+struct Point<const N: usize = 2>([i64; N]);
+
+impl<const N: usize> Point<N> {
+    fn within_bounds(&self, map: &Map<N>) -> bool {
+        map.bounds.contains(self)
+    }
+
+    fn diff(&self, other: &Self) -> [i64; N] {
+        std::array::from_fn(|i| self.0[i] - other.0[i])
+    }
+
+    /// `self + step * num / den`, the caller making sure the division is exact.
+    fn offset(&self, step: &[i64; N], num: i64, den: i64) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] + step[i] * num / den))
+    }
+}
+
+/// Inclusive bounds in each dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds<const N: usize = 2> {
+    min: [i64; N],
+    max: [i64; N],
 }
 
-impl Point {
-    fn within_bounds(&self, map: &Map) -> bool {
-        self.x >= map.min_x && self.x <= map.max_x && self.y >= map.min_y && self.y <= map.max_y
+impl<const N: usize> Bounds<N> {
+    fn contains(&self, point: &Point<N>) -> bool {
+        (0..N).all(|i| point.0[i] >= self.min[i] && point.0[i] <= self.max[i])
     }
 }
 
-struct Map {
-    min_x: i64,
-    max_x: i64,
-    min_y: i64,
-    max_y: i64,
-    antennas: HashMap<char, Vec<Point>>,
+#[derive(Debug, Clone)]
+struct Map<const N: usize = 2> {
+    bounds: Bounds<N>,
+    antennas: HashMap<char, Vec<Point<N>>>,
 }
 
-fn parse_input(input: &str) -> Map {
-    let mut max_x = 0;
-    let mut max_y = 0;
-    let mut antennas: HashMap<char, Vec<Point>> = HashMap::new();
+/// Parse a single 2D grid, returning its width, height and antennas as `(x, y, frequency)`.
+fn parse_grid(input: &str) -> (i64, i64, Vec<(i64, i64, char)>) {
+    let mut width = 0;
+    let mut height = 0;
+    let mut antennas = Vec::new();
 
     for (y, row) in input.lines().enumerate() {
-        max_y = y as i64;
-        if max_x == 0 {
-            max_x = row.chars().count() as i64 - 1;
+        height = y as i64 + 1;
+        if width == 0 {
+            width = row.chars().count() as i64;
         }
         for (x, col) in row.chars().enumerate() {
             if col != '.' {
-                let antenna = Point {
-                    x: x as i64,
-                    y: y as i64,
-                };
-                antennas.entry(col).or_default().push(antenna);
+                antennas.push((x as i64, y as i64, col));
             }
         }
     }
 
+    (width, height, antennas)
+}
+
+fn parse_input(input: &str) -> Map {
+    let (width, height, grid) = parse_grid(input);
+    let mut antennas: HashMap<char, Vec<Point>> = HashMap::new();
+    for (x, y, frequency) in grid {
+        antennas.entry(frequency).or_default().push(Point([x, y]));
+    }
+
     Map {
-        min_x: 0,
-        max_x,
-        min_y: 0,
-        max_y,
+        bounds: Bounds {
+            min: [0, 0],
+            max: [width - 1, height - 1],
+        },
         antennas,
     }
 }
 
-mod part1 {
-    use super::*;
+/// Parse a 3D volume given as 2D layers separated by blank lines, the layer index being `z`.
+fn parse_layers(input: &str) -> Map<3> {
+    let mut max = [0, 0, -1];
+    let mut antennas: HashMap<char, Vec<Point<3>>> = HashMap::new();
 
-    pub fn get_antinodes(antenna1: &Point, antenna2: &Point) -> (Point, Point) {
-        let diff_x = antenna1.x - antenna2.x;
-        let diff_y = antenna1.y - antenna2.y;
+    for (z, layer) in input.trim_end().split("\n\n").enumerate() {
+        let (width, height, grid) = parse_grid(layer);
+        max = [max[0].max(width - 1), max[1].max(height - 1), z as i64];
+        for (x, y, frequency) in grid {
+            antennas
+                .entry(frequency)
+                .or_default()
+                .push(Point([x, y, z as i64]));
+        }
+    }
 
-        (
-            Point {
-                x: antenna1.x + diff_x,
-                y: antenna1.y + diff_y,
-            },
-            Point {
-                x: antenna2.x - diff_x,
-                y: antenna2.y - diff_y,
-            },
-        )
+    Map {
+        bounds: Bounds {
+            min: [0, 0, 0],
+            max,
+        },
+        antennas,
     }
+}
 
-    pub fn solve1(input: &Map) -> usize {
-        let mut locations = HashSet::new();
-        for (_, antennas) in input.antennas.iter() {
-            for i in 0..antennas.len() {
-                for j in i + 1..antennas.len() {
-                    let (a1, a2) = get_antinodes(&antennas[i], &antennas[j]);
-                    if a1.within_bounds(input) {
-                        locations.insert(a1);
-                    }
-                    if a2.within_bounds(input) {
-                        locations.insert(a2);
-                    }
-                }
-            }
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// How antinodes are placed on the line through a pair of antennas. Every candidate is
+/// `antenna2 + t * (antenna1 - antenna2)` for some rational `t`.
+#[derive(Debug, Clone)]
+struct AntinodeModel {
+    /// Points where one antenna is `r` times as far away as the other, for each `r`, both
+    /// between and outside the pair. `None` allows any distance at all.
+    ratios: Option<Vec<u32>>,
+    /// Allow `t` to be fractional when the point still lands on the grid, i.e. step by the
+    /// separation divided by its gcd, rather than only by whole separations.
+    lattice_steps: bool,
+}
+
+impl AntinodeModel {
+    /// Exactly twice as far from one antenna as the other, whole separations only.
+    fn part1() -> Self {
+        Self {
+            ratios: Some(vec![2]),
+            lattice_steps: false,
         }
+    }
 
-        locations.len()
+    /// Any whole number of separations from either antenna.
+    fn part2() -> Self {
+        Self {
+            ratios: None,
+            lattice_steps: false,
+        }
     }
-}
 
-mod part2 {
-    use super::*;
+    fn antinodes<const N: usize>(
+        &self,
+        antenna1: &Point<N>,
+        antenna2: &Point<N>,
+        map: &Map<N>,
+    ) -> Vec<Point<N>> {
+        let diff = antenna1.diff(antenna2);
+        let divisor = if self.lattice_steps {
+            diff.iter().fold(0, |acc, d| gcd(acc, *d)).max(1)
+        } else {
+            1
+        };
 
-    fn get_antinodes(antenna1: &Point, antenna2: &Point, map: &Map) -> Vec<Point> {
-        let diff_x = antenna1.x - antenna2.x;
-        let diff_y = antenna1.y - antenna2.y;
+        let Some(ratios) = &self.ratios else {
+            let mut antinodes = vec![];
+            for direction in [1, -1] {
+                let mut k = if direction == 1 { 0 } else { -1 };
+                let mut cur_node = antenna2.offset(&diff, k, divisor);
+                while cur_node.within_bounds(map) {
+                    antinodes.push(cur_node);
+                    k += direction;
+                    cur_node = antenna2.offset(&diff, k, divisor);
+                }
+            }
+            return antinodes;
+        };
 
         let mut antinodes = vec![];
+        for r in ratios.iter().map(|r| i64::from(*r)) {
+            // `t` as numerator/denominator, solving |t - 1| = r|t| and |t| = r|t - 1|
+            let mut candidates = vec![(1, 1 + r), (r, 1 + r)];
+            if r != 1 {
+                candidates.extend([(r, r - 1), (-1, r - 1)]);
+            }
 
-        // adding diff
-        let mut cur_node = *antenna1;
-        while cur_node.within_bounds(map) {
-            antinodes.push(cur_node);
-            cur_node.x += diff_x;
-            cur_node.y += diff_y
-        }
-
-        // subtracting diff
-        let mut cur_node = *antenna2;
-        while cur_node.within_bounds(map) {
-            antinodes.push(cur_node);
-            cur_node.x -= diff_x;
-            cur_node.y -= diff_y
+            for (num, den) in candidates {
+                // `t * diff` has to land on the grid, which needs `den | num * divisor` when
+                // stepping by `diff / divisor`
+                if (num * divisor) % den != 0 {
+                    continue;
+                }
+                let antinode = antenna2.offset(&diff, num, den);
+                if antinode.within_bounds(map) && !antinodes.contains(&antinode) {
+                    antinodes.push(antinode);
+                }
+            }
         }
-
         antinodes
     }
 
-    pub fn solve2(input: &Map) -> usize {
+    fn locations<const N: usize>(&self, input: &Map<N>) -> HashSet<Point<N>> {
         let mut locations = HashSet::new();
         for (_, antennas) in input.antennas.iter() {
             for i in 0..antennas.len() {
                 for j in i + 1..antennas.len() {
-                    for antinode in get_antinodes(&antennas[i], &antennas[j], input) {
-                        locations.insert(antinode);
-                    }
+                    locations.extend(self.antinodes(&antennas[i], &antennas[j], input));
                 }
             }
         }
+        locations
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FrequencyReport {
+    frequency: char,
+    antennas: usize,
+    pairs: usize,
+    /// Unique antinodes inside the map
+    antinodes: usize,
+    /// How many of those are also antinodes of some other frequency
+    overlap: usize,
+}
+
+fn frequency_report<const N: usize>(input: &Map<N>, model: &AntinodeModel) -> Vec<FrequencyReport> {
+    let by_frequency: HashMap<char, HashSet<Point<N>>> = input
+        .antennas
+        .keys()
+        .map(|frequency| {
+            let single = Map {
+                antennas: HashMap::from([(*frequency, input.antennas[frequency].clone())]),
+                ..*input
+            };
+            (*frequency, model.locations(&single))
+        })
+        .collect();
 
-        locations.len()
+    let mut report: Vec<FrequencyReport> = by_frequency
+        .iter()
+        .map(|(frequency, antinodes)| {
+            let antennas = input.antennas[frequency].len();
+            let overlap = antinodes
+                .iter()
+                .filter(|p| {
+                    by_frequency
+                        .iter()
+                        .any(|(other, nodes)| other != frequency && nodes.contains(p))
+                })
+                .count();
+            FrequencyReport {
+                frequency: *frequency,
+                antennas,
+                pairs: antennas * antennas.saturating_sub(1) / 2,
+                antinodes: antinodes.len(),
+                overlap,
+            }
+        })
+        .collect();
+    report.sort_by_key(|r| r.frequency);
+    report
+}
+
+/// Draw the map as in the puzzle, with `#` for antinodes. Antennas are drawn over any antinode
+/// sharing their location.
+fn render(input: &Map, antinodes: &HashSet<Point>) -> String {
+    let antennas: HashMap<Point, char> = input
+        .antennas
+        .iter()
+        .flat_map(|(frequency, points)| points.iter().map(|p| (*p, *frequency)))
+        .collect();
+
+    let mut output = String::new();
+    for y in input.bounds.min[1]..=input.bounds.max[1] {
+        for x in input.bounds.min[0]..=input.bounds.max[0] {
+            let point = Point([x, y]);
+            output.push(match antennas.get(&point) {
+                Some(frequency) => *frequency,
+                None if antinodes.contains(&point) => '#',
+                None => '.',
+            });
+        }
+        output.push('\n');
     }
+    output
+}
+
+fn solve1(input: &Map) -> usize {
+    AntinodeModel::part1().locations(input).len()
+}
+
+fn solve2(input: &Map) -> usize {
+    AntinodeModel::part2().locations(input).len()
 }
 
 fn main() {
     let parsed = parse_input(include_str!("input.txt"));
 
-    println!("Part 1: {}", part1::solve1(&parsed));
-    println!("Part 2: {}", part2::solve2(&parsed));
+    println!("Part 1: {}", solve1(&parsed));
+    println!("Part 2: {}", solve2(&parsed));
+
+    // `--layers <file>` solves a 3D volume of stacked layers instead
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--layers") {
+        let path = args.get(idx + 1).expect("Missing layers file");
+        let volume = parse_layers(&std::fs::read_to_string(path).expect("Failed to read layers"));
+        println!(
+            "3D Part 1: {}",
+            AntinodeModel::part1().locations(&volume).len()
+        );
+        println!(
+            "3D Part 2: {}",
+            AntinodeModel::part2().locations(&volume).len()
+        );
+    }
+
+    if args.iter().any(|arg| arg == "--report") {
+        for (part, model) in [(1, AntinodeModel::part1()), (2, AntinodeModel::part2())] {
+            println!("Part {part}:");
+            for r in frequency_report(&parsed, &model) {
+                println!(
+                    "  {}: {} antennas, {} pairs, {} antinodes, {} shared with other frequencies",
+                    r.frequency, r.antennas, r.pairs, r.antinodes, r.overlap
+                );
+            }
+            print!("{}", render(&parsed, &model.locations(&parsed)));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -151,44 +327,219 @@ mod tests {
     #[test]
     fn parse_test() {
         let parsed = parse_input(INPUT1);
-        assert_eq!(
-            parsed.antennas[&'a'],
-            &[Point { x: 4, y: 3 }, Point { x: 5, y: 5 }]
-        );
-        assert_eq!(parsed.max_x, 9);
-        assert_eq!(parsed.max_y, 9);
+        assert_eq!(parsed.antennas[&'a'], &[Point([4, 3]), Point([5, 5])]);
+        assert_eq!(parsed.bounds.max, [9, 9]);
     }
 
     #[test]
     fn test_antinodes() {
-        let antenna1 = Point { x: 4, y: 3 };
-        let antenna2 = Point { x: 5, y: 5 };
+        let map = parse_input(INPUT1);
+        let antenna1 = Point([4, 3]);
+        let antenna2 = Point([5, 5]);
 
+        let model = AntinodeModel::part1();
         assert_eq!(
-            part1::get_antinodes(&antenna1, &antenna2),
-            (Point { x: 3, y: 1 }, Point { x: 6, y: 7 })
+            model.antinodes(&antenna1, &antenna2, &map),
+            &[Point([3, 1]), Point([6, 7])]
         );
         assert_eq!(
-            part1::get_antinodes(&antenna2, &antenna1),
-            (Point { x: 6, y: 7 }, Point { x: 3, y: 1 })
+            model.antinodes(&antenna2, &antenna1, &map),
+            &[Point([6, 7]), Point([3, 1])]
+        );
+    }
+
+    fn diagonal(model: &AntinodeModel) -> Vec<i64> {
+        // Antennas 3 apart on the diagonal of an empty 10x10 map
+        let map = parse_input(INPUT1);
+        let mut antinodes: Vec<i64> = model
+            .antinodes(&Point([3, 3]), &Point([0, 0]), &map)
+            .iter()
+            .map(|p| p.0[0])
+            .collect();
+        antinodes.sort();
+        antinodes
+    }
+
+    #[test]
+    fn test_antinode_models() {
+        // Whole separations only, as in the puzzle
+        assert_eq!(diagonal(&AntinodeModel::part1()), &[6]);
+        assert_eq!(diagonal(&AntinodeModel::part2()), &[0, 3, 6, 9]);
+
+        // Lattice steps pick up the points a third of the way between the antennas too
+        let lattice = |ratios| AntinodeModel {
+            ratios,
+            lattice_steps: true,
+        };
+        assert_eq!(diagonal(&lattice(Some(vec![2]))), &[1, 2, 6]);
+        assert_eq!(diagonal(&lattice(None)), (0..10).collect::<Vec<_>>());
+
+        // Other ratios, 3:1 lands a quarter or half a separation away
+        assert_eq!(diagonal(&lattice(Some(vec![3]))), &[]);
+        // 0:1 is just the antennas themselves
+        assert_eq!(diagonal(&lattice(Some(vec![0]))), &[0, 3]);
+        let map = parse_input(INPUT1);
+        let wide = lattice(Some(vec![1, 3])).antinodes(&Point([4, 0]), &Point([0, 0]), &map);
+        assert_eq!(
+            wide.iter().map(|p| p.0[0]).collect::<Vec<_>>(),
+            &[2, 1, 3, 6]
         );
     }
 
     #[test]
     fn test1() {
         let parsed = parse_input(INPUT1);
-        assert_eq!(part1::solve1(&parsed), 2);
+        assert_eq!(solve1(&parsed), 2);
 
         let parsed = parse_input(INPUT2);
-        assert_eq!(part1::solve1(&parsed), 14);
+        assert_eq!(solve1(&parsed), 14);
     }
 
     #[test]
     fn test2() {
         let parsed = parse_input(INPUT3);
-        assert_eq!(part2::solve2(&parsed), 9);
+        assert_eq!(solve2(&parsed), 9);
+
+        let parsed = parse_input(INPUT2);
+        assert_eq!(solve2(&parsed), 34);
+    }
+
+    #[test]
+    fn test_render() {
+        let parsed = parse_input(INPUT1);
+        assert_eq!(
+            render(&parsed, &AntinodeModel::part1().locations(&parsed)),
+            "..........\n\
+             ...#......\n\
+             ..........\n\
+             ....a.....\n\
+             ..........\n\
+             .....a....\n\
+             ..........\n\
+             ......#...\n\
+             ..........\n\
+             ..........\n"
+        );
+
+        let parsed = parse_input(INPUT2);
+        assert_eq!(
+            render(&parsed, &AntinodeModel::part1().locations(&parsed)),
+            "......#....#\n\
+             ...#....0...\n\
+             ....#0....#.\n\
+             ..#....0....\n\
+             ....0....#..\n\
+             .#....A.....\n\
+             ...#........\n\
+             #......#....\n\
+             ........A...\n\
+             .........A..\n\
+             ..........#.\n\
+             ..........#.\n"
+        );
+        assert_eq!(
+            render(&parsed, &AntinodeModel::part2().locations(&parsed)),
+            "##....#....#\n\
+             .#.#....0...\n\
+             ..#.#0....#.\n\
+             ..##...0....\n\
+             ....0....#..\n\
+             .#...#A....#\n\
+             ...#..#.....\n\
+             #....#.#....\n\
+             ..#.....A...\n\
+             ....#....A..\n\
+             .#........#.\n\
+             ...#......##\n"
+        );
+
+        let parsed = parse_input(INPUT3);
+        assert_eq!(
+            render(&parsed, &AntinodeModel::part2().locations(&parsed)),
+            "T....#....\n\
+             ...T......\n\
+             .T....#...\n\
+             .........#\n\
+             ..#.......\n\
+             ..........\n\
+             ...#......\n\
+             ..........\n\
+             ....#.....\n\
+             ..........\n"
+        );
+    }
 
+    #[test]
+    fn test_frequency_report() {
         let parsed = parse_input(INPUT2);
-        assert_eq!(part2::solve2(&parsed), 34);
+        assert_eq!(
+            frequency_report(&parsed, &AntinodeModel::part1()),
+            &[
+                FrequencyReport {
+                    frequency: '0',
+                    antennas: 4,
+                    pairs: 6,
+                    antinodes: 10,
+                    overlap: 1,
+                },
+                FrequencyReport {
+                    frequency: 'A',
+                    antennas: 3,
+                    pairs: 3,
+                    antinodes: 5,
+                    overlap: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        let layer = |row: usize, col: usize| {
+            let mut grid = vec![vec!['.'; 4]; 4];
+            grid[row][col] = 'a';
+            grid.iter()
+                .map(|r| r.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let empty = "....\n....\n....\n....";
+        let input = [
+            layer(0, 0),
+            layer(1, 1),
+            empty.to_string(),
+            empty.to_string(),
+        ]
+        .join("\n\n");
+
+        let volume = parse_layers(&input);
+        assert_eq!(volume.bounds.max, [3, 3, 3]);
+        assert_eq!(volume.antennas[&'a'], &[Point([0, 0, 0]), Point([1, 1, 1])]);
+
+        assert_eq!(
+            AntinodeModel::part1().locations(&volume),
+            HashSet::from([Point([2, 2, 2])])
+        );
+        assert_eq!(AntinodeModel::part2().locations(&volume).len(), 4);
+        assert_eq!(
+            frequency_report(&volume, &AntinodeModel::part2())[0].antinodes,
+            4
+        );
+
+        // Antennas two apart on the space diagonal, only lattice steps see the midpoint
+        let input = [
+            layer(0, 0),
+            empty.to_string(),
+            layer(2, 2),
+            empty.to_string(),
+        ]
+        .join("\n\n");
+        let volume = parse_layers(&input);
+        let lattice = AntinodeModel {
+            ratios: None,
+            lattice_steps: true,
+        };
+        assert_eq!(AntinodeModel::part2().locations(&volume).len(), 2);
+        assert_eq!(lattice.locations(&volume).len(), 4);
     }
 }
@@ -1,9 +1,11 @@
 use std::collections::VecDeque;
 
 use nom::branch::alt;
-use nom::bytes::complete::take;
-use nom::combinator::map;
-use nom::{sequence::pair, IResult};
+use nom::character::complete::{char, multispace0, multispace1, satisfy, u64 as parse_u64};
+use nom::combinator::{map, recognize};
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct FileData {
@@ -17,152 +19,635 @@ enum Record {
     Free(u64),
 }
 
-fn parse_input(mut input: &str) -> VecDeque<Record> {
-    let mut filesystem = VecDeque::new();
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    /// Something other than a size at byte `offset`, `found` is empty at the end of the input
+    InvalidSize {
+        offset: usize,
+        found: String,
+    },
+    EmptyFile {
+        id: u64,
+    },
+    /// The disk has more than `u64::MAX` blocks
+    TooLarge,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidSize { offset, found } if found.is_empty() => {
+                write!(f, "expected a size at byte {offset}, found end of input")
+            }
+            ParseError::InvalidSize { offset, found } => {
+                write!(f, "expected a size at byte {offset}, found {found:?}")
+            }
+            ParseError::EmptyFile { id } => write!(f, "file {id} has no blocks"),
+            ParseError::TooLarge => write!(f, "disk is larger than u64::MAX blocks"),
+        }
+    }
+}
 
-    let mut id = 0;
-    while !input.is_empty() {
-        let (rem, (file_size, free_size)) = parse_record(input).unwrap();
-        input = rem;
+/// Parse a disk map, either the classic one digit per size (`2333133121414131402`) or sizes of
+/// any width separated by commas and/or whitespace (`2, 33, 10 1`).
+fn parse_input(input: &str) -> Result<VecDeque<Record>, ParseError> {
+    let is_separator = |c: char| c == ',' || c.is_whitespace();
+    let input = input.trim_end();
+    let (rem, sizes) = if input.contains(is_separator) {
+        let (rem, sizes) = parse_sizes(input).unwrap_or((input, Vec::new()));
+        // Point past the separator the list stopped at, to the size that didn't parse
+        let rem = rem.trim_start();
+        let rem = rem.strip_prefix(',').unwrap_or(rem).trim_start();
+        (rem, sizes)
+    } else {
+        parse_digits(input).unwrap_or((input, Vec::new()))
+    };
+    if let Some(c) = rem.chars().next() {
+        let found = if c.is_ascii_digit() {
+            rem.split(is_separator).next().unwrap_or(rem)
+        } else {
+            &rem[..c.len_utf8()]
+        };
+        return Err(ParseError::InvalidSize {
+            offset: input.len() - rem.len(),
+            found: found.to_string(),
+        });
+    }
+    if input.ends_with(',') {
+        return Err(ParseError::InvalidSize {
+            offset: input.len(),
+            found: String::new(),
+        });
+    }
 
-        assert!(file_size > 0);
+    let mut filesystem = VecDeque::new();
+    let mut disk_size: u64 = 0;
+    for (id, record) in (0..).zip(sizes.chunks(2)) {
+        let (file_size, free_size) = (record[0], record.get(1).copied().unwrap_or(0));
+        if file_size == 0 {
+            return Err(ParseError::EmptyFile { id });
+        }
+        disk_size = disk_size
+            .checked_add(file_size)
+            .and_then(|size| size.checked_add(free_size))
+            .ok_or(ParseError::TooLarge)?;
 
         filesystem.push_back(Record::File(FileData {
             id,
             size: file_size,
         }));
-        id += 1;
-
         if free_size > 0 {
             filesystem.push_back(Record::Free(free_size));
         }
     }
 
-    filesystem
+    Ok(filesystem)
 }
 
-fn parse_record(input: &str) -> IResult<&str, (u64, u64)> {
-    alt((pair(parse_num, parse_num), map(parse_num, |v| (v, 0))))(input)
+fn parse_digits(input: &str) -> IResult<&str, Vec<u64>> {
+    many0(map(satisfy(|c| c.is_ascii_digit()), |c| {
+        c.to_digit(10).unwrap() as u64
+    }))(input)
 }
 
-fn parse_num(input: &str) -> IResult<&str, u64> {
-    map(take(1_usize), |v: &str| v.parse().unwrap())(input)
+fn parse_sizes(input: &str) -> IResult<&str, Vec<u64>> {
+    let separator = alt((
+        recognize(tuple((multispace0, char(','), multispace0))),
+        multispace1,
+    ));
+    preceded(multispace0, separated_list0(separator, parse_u64))(input)
 }
 
-mod part1 {
-    use super::*;
+/// Every block on disk, holding the ID of the file stored there or `None` if free. This takes
+/// memory for every block, so it's only for looking at small disks.
+type Layout = Vec<Option<u64>>;
 
-    fn take_from_end(filesystem: &mut VecDeque<Record>) -> Option<u64> {
-        let mut last = filesystem.pop_back()?;
+fn layout(input: &VecDeque<Record>) -> Layout {
+    let mut blocks = Vec::new();
+    for record in input {
+        match record {
+            Record::File(FileData { id, size }) => {
+                blocks.extend(std::iter::repeat_n(Some(*id), *size as usize))
+            }
+            Record::Free(size) => blocks.extend(std::iter::repeat_n(None, *size as usize)),
+        }
+    }
+    blocks
+}
+
+/// Sizes can be anything up to `u64::MAX`, so a checksum can be too big to represent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChecksumOverflow;
 
-        while let Record::Free(_) = last {
-            last = filesystem.pop_back().unwrap();
+impl std::fmt::Display for ChecksumOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "checksum is larger than u64::MAX")
+    }
+}
+
+fn layout_checksum(layout: &Layout) -> Result<u64, ChecksumOverflow> {
+    layout
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| block.map(|id| (i as u64).checked_mul(id)))
+        .try_fold(0u64, |total, block| total.checked_add(block?))
+        .ok_or(ChecksumOverflow)
+}
+
+/// Render a layout in the puzzle's notation, e.g. `00...111...2`. IDs of 10 or more don't fit in
+/// one character, so they are shown in brackets, `[10]`, once per block.
+fn render(layout: &Layout) -> String {
+    layout
+        .iter()
+        .map(|block| match block {
+            None => ".".to_string(),
+            Some(id) if *id < 10 => id.to_string(),
+            Some(id) => format!("[{id}]"),
+        })
+        .collect()
+}
+
+/// Move single blocks from the end of the disk into the leftmost free block until there are no
+/// gaps, calling `on_step` with the layout before the first move and after each one.
+fn compact_blocks(mut layout: Layout, mut on_step: impl FnMut(&Layout)) -> Layout {
+    on_step(&layout);
+
+    let mut left = 0;
+    let mut right = layout.len();
+    loop {
+        while left < right && layout[left].is_some() {
+            left += 1;
+        }
+        while right > left && layout[right - 1].is_none() {
+            right -= 1;
+        }
+        if right <= left {
+            break;
         }
+        right -= 1;
+        layout.swap(left, right);
+        on_step(&layout);
+    }
+
+    layout
+}
+
+/// A file's final location on disk after compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PlacedFile {
+    id: u64,
+    start: u64,
+    size: u64,
+}
+
+/// Lay out placed files on a disk of `len` blocks.
+fn file_layout(files: &[PlacedFile], len: usize) -> Layout {
+    let mut layout = vec![None; len];
+    for file in files {
+        let start = file.start as usize;
+        layout[start..start + file.size as usize].fill(Some(file.id));
+    }
+    layout
+}
 
-        let Record::File(FileData { id, mut size }) = last else {
-            panic!()
+fn checksum(files: &[PlacedFile]) -> Result<u64, ChecksumOverflow> {
+    // id * (start + start + 1 + .. + start + size - 1), halving whichever of size and size - 1
+    // is even so nothing overflows before it has to
+    let file_checksum = |f: &PlacedFile| -> Option<u64> {
+        let below = f.size.saturating_sub(1);
+        let triangle = if f.size.is_multiple_of(2) {
+            (f.size / 2).checked_mul(below)?
+        } else {
+            f.size.checked_mul(below / 2)?
         };
+        let positions = f.start.checked_mul(f.size)?.checked_add(triangle)?;
+        f.id.checked_mul(positions)
+    };
+
+    files
+        .iter()
+        .try_fold(0u64, |total, f| total.checked_add(file_checksum(f)?))
+        .ok_or(ChecksumOverflow)
+}
+
+/// Fill each free span from the front of the disk with blocks taken from the end, as
+/// [`compact_blocks`] does, but a whole run of blocks at a time. Files split across gaps are
+/// returned as one run per piece.
+fn compact_runs(input: &VecDeque<Record>) -> Vec<PlacedFile> {
+    let mut input = input.clone();
+    let mut runs = Vec::new();
+    let mut position = 0;
+
+    while let Some(record) = input.pop_front() {
+        let mut free = match record {
+            Record::File(FileData { id, size }) => {
+                runs.push(PlacedFile {
+                    id,
+                    start: position,
+                    size,
+                });
+                position += size;
+                continue;
+            }
+            Record::Free(size) => size,
+        };
+
+        while free > 0 {
+            let Some(Record::File(FileData { id, size })) = input.pop_back() else {
+                // Free space at the end, or nothing left at all
+                if input.is_empty() {
+                    break;
+                }
+                continue;
+            };
+            let moved = size.min(free);
+            runs.push(PlacedFile {
+                id,
+                start: position,
+                size: moved,
+            });
+            position += moved;
+            free -= moved;
+            if moved < size {
+                input.push_back(Record::File(FileData {
+                    id,
+                    size: size - moved,
+                }));
+            }
+        }
+    }
+
+    runs
+}
 
-        size -= 1;
+fn solve1(input: &VecDeque<Record>) -> Result<u64, ChecksumOverflow> {
+    checksum(&compact_runs(input))
+}
 
-        if size > 0 {
-            filesystem.push_back(Record::File(FileData { id, size }));
+/// Free spans in disk order, under a max segment tree so the leftmost span with room for a file
+/// can be found in `O(log n)`.
+struct FreeSpans {
+    starts: Vec<u64>,
+    /// Leaves hold each span's remaining size, internal nodes the max of their children
+    tree: Vec<u64>,
+    leaves: usize,
+}
+
+impl FreeSpans {
+    fn new(spans: &[(u64, u64)]) -> Self {
+        let leaves = spans.len().next_power_of_two();
+        let mut tree = vec![0; 2 * leaves];
+        for (i, (_, size)) in spans.iter().enumerate() {
+            tree[leaves + i] = *size;
+        }
+        for i in (1..leaves).rev() {
+            tree[i] = tree[2 * i].max(tree[2 * i + 1]);
         }
 
-        Some(id)
+        Self {
+            starts: spans.iter().map(|(start, _)| *start).collect(),
+            tree,
+            leaves,
+        }
     }
 
-    pub fn solve1(mut input: VecDeque<Record>) -> u64 {
-        let mut checksum = 0;
-        let mut position = 0;
+    /// Index of the leftmost span with at least `size` blocks free.
+    fn leftmost_fit(&self, size: u64) -> Option<usize> {
+        if self.tree[1] < size {
+            return None;
+        }
+        let mut node = 1;
+        while node < self.leaves {
+            node = if self.tree[2 * node] >= size {
+                2 * node
+            } else {
+                2 * node + 1
+            };
+        }
+        Some(node - self.leaves)
+    }
 
-        while let Some(record) = input.pop_front() {
-            match record {
-                Record::File(FileData { id, size }) => {
-                    for i in 0..size {
-                        checksum += id * (position + i);
-                    }
-                    position += size;
-                }
-                Record::Free(mut size) => {
-                    size -= 1;
-                    if let Some(id) = take_from_end(&mut input) {
-                        checksum += position * id;
-                        position += 1;
-                        if size > 0 {
-                            input.push_front(Record::Free(size));
-                        }
-                    }
-                }
+    /// Use up `size` blocks from the front of span `idx`, returning where they start.
+    fn take(&mut self, idx: usize, size: u64) -> u64 {
+        let start = self.starts[idx];
+        self.starts[idx] += size;
+
+        let mut node = self.leaves + idx;
+        self.tree[node] -= size;
+        while node > 1 {
+            node /= 2;
+            self.tree[node] = self.tree[2 * node].max(self.tree[2 * node + 1]);
+        }
+
+        start
+    }
+}
+
+/// Files and free spans, as `(start, size)`, where they start out on disk.
+fn disk_spans(input: &VecDeque<Record>) -> (Vec<PlacedFile>, Vec<(u64, u64)>) {
+    let mut files = Vec::new();
+    let mut spans = Vec::new();
+    let mut position = 0;
+    for record in input {
+        match record {
+            Record::File(FileData { id, size }) => {
+                files.push(PlacedFile {
+                    id: *id,
+                    start: position,
+                    size: *size,
+                });
+                position += size;
+            }
+            Record::Free(size) => {
+                spans.push((position, *size));
+                position += size;
             }
         }
-        checksum
     }
+    (files, spans)
 }
 
-mod part2 {
+/// Give each file one chance to move, in decreasing ID order, to wherever `place` says it should
+/// start. `on_move` sees every file's location before the first move and after each one. Returns
+/// the files in disk order.
+///
+/// Space freed up by moving a file left is always to the right of every file still to move, so
+/// `place` never needs to hand it out again.
+fn move_files(
+    mut files: Vec<PlacedFile>,
+    mut place: impl FnMut(&PlacedFile) -> Option<u64>,
+    mut on_move: impl FnMut(&[PlacedFile]),
+) -> Vec<PlacedFile> {
+    files.sort_by_key(|f| std::cmp::Reverse(f.id));
+    on_move(&files);
+    for i in 0..files.len() {
+        if let Some(start) = place(&files[i]) {
+            assert!(start < files[i].start, "files only move left");
+            files[i].start = start;
+            on_move(&files);
+        }
+    }
+
+    files.sort_by_key(|f| f.start);
+    files
+}
+
+/// Move each whole file once, in decreasing ID order, to the leftmost free span to its left
+/// with room for it, calling `on_move` with every file's location before the first move and after
+/// each one.
+fn compact_files(input: &VecDeque<Record>, on_move: impl FnMut(&[PlacedFile])) -> Vec<PlacedFile> {
+    let (files, spans) = disk_spans(input);
+    let mut free = FreeSpans::new(&spans);
+    move_files(
+        files,
+        |file| {
+            let idx = free.leftmost_fit(file.size)?;
+            (free.starts[idx] < file.start).then(|| free.take(idx, file.size))
+        },
+        on_move,
+    )
+}
+
+fn solve2(input: &VecDeque<Record>) -> Result<u64, ChecksumOverflow> {
+    checksum(&compact_files(input, |_| {}))
+}
+
+mod strategy {
     use super::*;
 
-    fn find_file_that_fits(free_space: u64, filesystem: &mut VecDeque<Record>) -> Option<FileData> {
-        let mut found_idx = None;
-        for (i, record) in filesystem.iter().enumerate().rev() {
-            match record {
-                Record::File(file_data) => {
-                    if file_data.size <= free_space {
-                        found_idx = Some(i);
-                        break;
-                    }
-                }
-                Record::Free(_) => (),
-            }
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FreeSpan {
+        pub start: u64,
+        pub size: u64,
+    }
+
+    /// Decides where, if anywhere, each whole file moves during compaction.
+    pub trait CompactionStrategy {
+        fn name(&self) -> &'static str;
+
+        /// Pick one of `spans` (free space in disk order) for `file`, currently at `start`, or
+        /// `None` to leave it where it is.
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize>;
+    }
+
+    /// Indices of the spans to the left of `start` with room for `file`.
+    fn candidates<'a>(
+        file: &'a FileData,
+        start: u64,
+        spans: &'a [FreeSpan],
+    ) -> impl Iterator<Item = usize> + 'a {
+        spans
+            .iter()
+            .enumerate()
+            .take_while(move |(_, span)| span.start < start)
+            .filter(|(_, span)| span.size >= file.size)
+            .map(|(idx, _)| idx)
+    }
+
+    /// The leftmost span that fits, as in part 2.
+    pub struct FirstFit;
+
+    impl CompactionStrategy for FirstFit {
+        fn name(&self) -> &'static str {
+            "first-fit"
         }
 
-        if let Some(found_idx) = found_idx {
-            let record = filesystem.remove(found_idx).unwrap();
-            let Record::File(record) = record else {
-                panic!();
-            };
-            filesystem.insert(found_idx, Record::Free(record.size));
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize> {
+            candidates(file, start, spans).next()
+        }
+    }
+
+    /// The smallest span that fits, leftmost on ties.
+    pub struct BestFit;
 
-            return Some(record);
+    impl CompactionStrategy for BestFit {
+        fn name(&self) -> &'static str {
+            "best-fit"
+        }
+
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize> {
+            candidates(file, start, spans).min_by_key(|idx| spans[*idx].size)
         }
-        None
     }
 
-    pub fn solve2(mut input: VecDeque<Record>) -> u64 {
-        let mut checksum = 0;
-        let mut position = 0;
+    /// The largest span that fits, leftmost on ties.
+    pub struct WorstFit;
 
-        while let Some(record) = input.pop_front() {
-            match record {
-                Record::File(FileData { id, size }) => {
-                    for i in 0..size {
-                        checksum += id * (position + i);
-                    }
-                    position += size;
-                }
-                Record::Free(size) => {
-                    if let Some(file) = find_file_that_fits(size, &mut input) {
-                        let new_free = size - file.size;
-                        if new_free > 0 {
-                            input.push_front(Record::Free(new_free));
-                        }
-                        input.push_front(Record::File(file));
-                    } else {
-                        position += size;
-                    }
-                }
+    impl CompactionStrategy for WorstFit {
+        fn name(&self) -> &'static str {
+            "worst-fit"
+        }
+
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize> {
+            candidates(file, start, spans)
+                .min_by_key(|idx| (std::cmp::Reverse(spans[*idx].size), *idx))
+        }
+    }
+
+    /// The first span that fits at or after the previous placement, wrapping back to the start of
+    /// the disk.
+    #[derive(Default)]
+    pub struct NextFit {
+        cursor: usize,
+    }
+
+    impl CompactionStrategy for NextFit {
+        fn name(&self) -> &'static str {
+            "next-fit"
+        }
+
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize> {
+            let idx = candidates(file, start, spans)
+                .find(|idx| *idx >= self.cursor)
+                .or_else(|| candidates(file, start, spans).next())?;
+            self.cursor = idx;
+            Some(idx)
+        }
+    }
+
+    /// The span giving the biggest drop in checksum, only moving when there is one. The checksum
+    /// only drops when a file with a non-zero ID moves left, so this lands where first-fit does
+    /// but never bothers moving file 0.
+    pub struct ChecksumReducing;
+
+    impl CompactionStrategy for ChecksumReducing {
+        fn name(&self) -> &'static str {
+            "checksum-reducing"
+        }
+
+        fn choose(&mut self, file: &FileData, start: u64, spans: &[FreeSpan]) -> Option<usize> {
+            candidates(file, start, spans)
+                .map(|idx| {
+                    // Can be far bigger than any checksum, so compare in u128 and saturate beyond that
+                    let reduction = u128::from(file.id)
+                        .checked_mul(u128::from(file.size))
+                        .and_then(|r| r.checked_mul(u128::from(start - spans[idx].start)))
+                        .unwrap_or(u128::MAX);
+                    (reduction, idx)
+                })
+                .filter(|(reduction, _)| *reduction > 0)
+                .min_by_key(|(reduction, idx)| (std::cmp::Reverse(*reduction), *idx))
+                .map(|(_, idx)| idx)
+        }
+    }
+
+    pub fn all() -> Vec<Box<dyn CompactionStrategy>> {
+        vec![
+            Box::new(FirstFit),
+            Box::new(BestFit),
+            Box::new(WorstFit),
+            Box::new(NextFit::default()),
+            Box::new(ChecksumReducing),
+        ]
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct Metrics {
+        pub blocks_moved: u64,
+        pub files_moved: u64,
+        /// Runs of free blocks left on the disk, including any after the last file
+        pub free_fragments: u64,
+        pub largest_free_span: u64,
+    }
+
+    /// Move each whole file once, in decreasing ID order, wherever `strategy` chooses. Unlike
+    /// [`compact_files`] this looks at every span for every file, so it's quadratic.
+    pub fn compact_with(
+        input: &VecDeque<Record>,
+        strategy: &mut dyn CompactionStrategy,
+    ) -> (Vec<PlacedFile>, Metrics) {
+        let (files, spans) = disk_spans(input);
+        let len = files.iter().map(|f| f.size).sum::<u64>()
+            + spans.iter().map(|(_, size)| size).sum::<u64>();
+        let mut spans: Vec<_> = spans
+            .into_iter()
+            .map(|(start, size)| FreeSpan { start, size })
+            .collect();
+
+        let mut metrics = Metrics::default();
+        let files = move_files(
+            files,
+            |file| {
+                let data = FileData {
+                    id: file.id,
+                    size: file.size,
+                };
+                let idx = strategy.choose(&data, file.start, &spans)?;
+                let span = &mut spans[idx];
+                assert!(span.size >= file.size && span.start < file.start);
+
+                let start = span.start;
+                span.start += file.size;
+                span.size -= file.size;
+                metrics.blocks_moved += file.size;
+                metrics.files_moved += 1;
+                Some(start)
+            },
+            |_| {},
+        );
+
+        let mut gaps = Vec::new();
+        let mut end = 0;
+        for file in &files {
+            if file.start > end {
+                gaps.push(file.start - end);
             }
+            end = file.start + file.size;
+        }
+        if len > end {
+            gaps.push(len - end);
         }
-        checksum
+        metrics.free_fragments = gaps.len() as u64;
+        metrics.largest_free_span = gaps.into_iter().max().unwrap_or(0);
+
+        (files, metrics)
     }
 }
 
+fn show(checksum: Result<u64, ChecksumOverflow>) -> String {
+    checksum.map_or_else(|e| e.to_string(), |checksum| checksum.to_string())
+}
+
 fn main() {
-    let parsed = parse_input(include_str!("input.txt"));
+    let parsed = parse_input(include_str!("input.txt")).unwrap_or_else(|e| panic!("{e}"));
+
+    println!("Part 1: {}", show(solve1(&parsed)));
+    println!("Part 2: {}", show(solve2(&parsed)));
+
+    // `--render <disk map>` prints each step of both compactions for a (small) disk map
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--render") {
+        let input = parse_input(args.get(idx + 1).expect("Missing disk map"))
+            .unwrap_or_else(|e| panic!("{e}"));
+        let len = layout(&input).len();
+
+        println!("Part 1:");
+        let compacted = compact_blocks(layout(&input), |step| println!("{}", render(step)));
+        println!("Checksum: {}", show(layout_checksum(&compacted)));
+
+        println!("Part 2:");
+        let files = compact_files(&input, |files| {
+            println!("{}", render(&file_layout(files, len)))
+        });
+        let compacted = file_layout(&files, len);
+        assert_eq!(layout_checksum(&compacted), checksum(&files));
+        println!("Checksum: {}", show(checksum(&files)));
+    }
 
-    println!("Part 1: {}", part1::solve1(parsed.clone()));
-    println!("Part 2: {}", part2::solve2(parsed));
+    if args.iter().any(|arg| arg == "--strategies") {
+        for mut strategy in strategy::all() {
+            let (files, m) = strategy::compact_with(&parsed, strategy.as_mut());
+            println!(
+                "{}: checksum {}, {} blocks in {} files moved, {} free fragments, largest {}",
+                strategy.name(),
+                show(checksum(&files)),
+                m.blocks_moved,
+                m.files_moved,
+                m.free_fragments,
+                m.largest_free_span
+            );
+        }
+    }
 }
 
 #[cfg(test)]
@@ -173,21 +658,376 @@ mod tests {
 
     #[test]
     fn parse_test() {
-        let parsed = parse_input(INPUT);
+        let parsed = parse_input(INPUT).unwrap();
         assert_eq!(parsed[0], Record::File(FileData { id: 0, size: 2 }));
         assert_eq!(parsed[1], Record::Free(3));
         assert_eq!(parsed[17], Record::File(FileData { id: 9, size: 2 }));
     }
 
+    #[test]
+    fn parse_extended_test() {
+        // Trailing newlines are fine in either format
+        assert_eq!(
+            parse_input("2333133121414131402\n").unwrap(),
+            parse_input(INPUT).unwrap()
+        );
+        assert_eq!(
+            parse_input("2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2\n").unwrap(),
+            parse_input(INPUT).unwrap()
+        );
+
+        let parsed = parse_input("12, 0 ,345\n10\t1099511627776").unwrap();
+        assert_eq!(
+            parsed,
+            [
+                Record::File(FileData { id: 0, size: 12 }),
+                Record::File(FileData { id: 1, size: 345 }),
+                Record::Free(10),
+                Record::File(FileData {
+                    id: 2,
+                    size: 1 << 40
+                }),
+            ]
+        );
+        assert_eq!(
+            render(&layout(&parse_input("1 10 2").unwrap())),
+            "0..........11"
+        );
+
+        assert_eq!(parse_input("").unwrap(), []);
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let invalid = |offset, found: &str| ParseError::InvalidSize {
+            offset,
+            found: found.to_string(),
+        };
+        assert_eq!(parse_input("12a4"), Err(invalid(2, "a")));
+        assert_eq!(parse_input("12, x4"), Err(invalid(4, "x")));
+        assert_eq!(parse_input("1,2,,3"), Err(invalid(4, ",")));
+        assert_eq!(parse_input("1,2,"), Err(invalid(4, "")));
+        assert_eq!(
+            parse_input("1, 18446744073709551616"),
+            Err(invalid(3, "18446744073709551616"))
+        );
+        assert_eq!(parse_input("2,3,0,1"), Err(ParseError::EmptyFile { id: 1 }));
+        assert_eq!(
+            parse_input("18446744073709551615 1"),
+            Err(ParseError::TooLarge)
+        );
+        assert_eq!(
+            parse_input("1,2,,3").unwrap_err().to_string(),
+            "expected a size at byte 4, found \",\""
+        );
+    }
+
     #[test]
     fn test1() {
-        let parsed = parse_input(INPUT);
-        assert_eq!(part1::solve1(parsed), 1928);
+        let parsed = parse_input(INPUT).unwrap();
+        assert_eq!(solve1(&parsed), Ok(1928));
+    }
+
+    #[test]
+    fn test_layout() {
+        let parsed = parse_input(INPUT).unwrap();
+        assert_eq!(
+            render(&layout(&parsed)),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        assert_eq!(
+            render(&layout(&parse_input("12345").unwrap())),
+            "0..111....22222"
+        );
+        assert_eq!(render(&vec![Some(10), None, Some(2)]), "[10].2");
+    }
+
+    #[test]
+    fn test_compact_blocks_steps() {
+        let mut steps = Vec::new();
+        let compacted = compact_blocks(layout(&parse_input("12345").unwrap()), |step| {
+            steps.push(render(step))
+        });
+        assert_eq!(
+            steps,
+            [
+                "0..111....22222",
+                "02.111....2222.",
+                "022111....222..",
+                "0221112...22...",
+                "02211122..2....",
+                "022111222......",
+            ]
+        );
+        assert_eq!(render(&compacted), "022111222......");
+
+        let compacted = compact_blocks(layout(&parse_input(INPUT).unwrap()), |_| {});
+        assert_eq!(
+            render(&compacted),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(layout_checksum(&compacted), Ok(1928));
+        let runs = compact_runs(&parse_input(INPUT).unwrap());
+        assert_eq!(file_layout(&runs, compacted.len()), compacted);
+    }
+
+    #[test]
+    fn test_compact_files_steps() {
+        let parsed = parse_input(INPUT).unwrap();
+        let len = layout(&parsed).len();
+        let mut steps = Vec::new();
+        let files = compact_files(&parsed, |files| {
+            steps.push(render(&file_layout(files, len)))
+        });
+        assert_eq!(
+            steps,
+            [
+                "00...111...2...333.44.5555.6666.777.888899",
+                "0099.111...2...333.44.5555.6666.777.8888..",
+                "0099.1117772...333.44.5555.6666.....8888..",
+                "0099.111777244.333....5555.6666.....8888..",
+                "00992111777.44.333....5555.6666.....8888..",
+            ]
+        );
+
+        let compacted = file_layout(&files, len);
+        assert_eq!(layout_checksum(&compacted), Ok(2858));
+        assert_eq!(layout_checksum(&compacted), checksum(&files));
+    }
+
+    #[test]
+    fn test_strategies() {
+        use strategy::*;
+
+        let parsed = parse_input(INPUT).unwrap();
+        let len = layout(&parsed).len();
+        let run = |strategy: &mut dyn CompactionStrategy| {
+            let (files, metrics) = compact_with(&parsed, strategy);
+            (render(&file_layout(&files, len)), metrics)
+        };
+
+        let (first, metrics) = run(&mut FirstFit);
+        assert_eq!(first, "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!(
+            metrics,
+            Metrics {
+                blocks_moved: 8,
+                files_moved: 4,
+                free_fragments: 6,
+                largest_free_span: 5,
+            }
+        );
+        assert_eq!(run(&mut ChecksumReducing), (first.clone(), metrics));
+
+        // All the example's gaps that fit a file are the same size, so try a disk with a choice
+        let parsed = parse_input("13151221").unwrap();
+        let len = layout(&parsed).len();
+        let run = |strategy: &mut dyn CompactionStrategy| {
+            let (files, metrics) = compact_with(&parsed, strategy);
+            (render(&file_layout(&files, len)), metrics)
+        };
+        let results: Vec<_> = all()
+            .into_iter()
+            .map(|mut strategy| {
+                let (layout, metrics) = run(strategy.as_mut());
+                (
+                    strategy.name(),
+                    layout,
+                    metrics.blocks_moved,
+                    metrics.files_moved,
+                    metrics.free_fragments,
+                    metrics.largest_free_span,
+                )
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                ("first-fit", "03321...........".to_string(), 3, 2, 1, 11),
+                ("best-fit", "021........33...".to_string(), 4, 3, 2, 8),
+                ("worst-fit", "021..33.........".to_string(), 4, 3, 2, 9),
+                ("next-fit", "03321...........".to_string(), 3, 2, 1, 11),
+                (
+                    "checksum-reducing",
+                    "03321...........".to_string(),
+                    3,
+                    2,
+                    1,
+                    11
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checksum_reducing_large_spans() {
+        use strategy::*;
+
+        // Moving file 2 into either gap reduces the checksum by more than u64::MAX
+        let parsed = parse_input("1 1099511627776 1 1099511627776 1099511627776").unwrap();
+        let (files, metrics) = compact_with(&parsed, &mut ChecksumReducing);
+        assert_eq!(compact_with(&parsed, &mut FirstFit), (files.clone(), metrics));
+        assert!(files.contains(&PlacedFile {
+            id: 2,
+            start: 1,
+            size: 1 << 40
+        }));
+        assert_eq!(checksum(&files), Err(ChecksumOverflow));
+
+        let parsed = parse_input("1 1099511627776 1099511627776").unwrap();
+        let (files, _) = compact_with(&parsed, &mut ChecksumReducing);
+        assert_eq!(files[1].start, 1);
+    }
+
+    #[test]
+    fn test_next_fit_wraps() {
+        // 3 follows 44 into the gaps after it, leaving 2 to wrap back round to the first gap
+        let parsed = parse_input("1112111524").unwrap();
+        let len = layout(&parsed).len();
+        let (files, _) = strategy::compact_with(&parsed, &mut strategy::NextFit::default());
+        assert_eq!(render(&file_layout(&files, len)), "02144.3............");
+        let (files, _) = strategy::compact_with(&parsed, &mut strategy::FirstFit);
+        assert_eq!(render(&file_layout(&files, len)), "031442.............");
+    }
+
+    #[test]
+    fn test_first_fit_matches_compact_files() {
+        let input = generate(2001);
+        let parsed = parse_input(&input).unwrap();
+        let (files, _) = strategy::compact_with(&parsed, &mut strategy::FirstFit);
+        assert_eq!(files, compact_files(&parsed, |_| {}));
     }
 
     #[test]
     fn test2() {
-        let parsed = parse_input(INPUT);
-        assert_eq!(part2::solve2(parsed), 2858);
+        let parsed = parse_input(INPUT).unwrap();
+        assert_eq!(solve2(&parsed), Ok(2858));
+    }
+
+    /// Deterministic pseudo random disk map of `len` digits, files never empty.
+    fn generate(len: usize) -> String {
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        (0..len)
+            .map(|i| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let digit = (state >> 33) % 10;
+                let digit = if i % 2 == 0 { digit.max(1) } else { digit };
+                char::from_digit(digit as u32, 10).unwrap()
+            })
+            .collect()
+    }
+
+    /// Block by block version of the puzzle's whole file compaction.
+    fn move_whole_files(input: &str) -> u64 {
+        let mut blocks: Vec<Option<u64>> = Vec::new();
+        for (i, c) in input.chars().enumerate() {
+            let size = c.to_digit(10).unwrap() as usize;
+            let id = (i % 2 == 0).then_some(i as u64 / 2);
+            blocks.extend(std::iter::repeat_n(id, size));
+        }
+
+        let max_id = (input.len() as u64 - 1) / 2;
+        for id in (0..=max_id).rev() {
+            let start = blocks.iter().position(|b| *b == Some(id)).unwrap();
+            let size = blocks[start..]
+                .iter()
+                .take_while(|b| **b == Some(id))
+                .count();
+            let mut run = 0;
+            for i in 0..start {
+                run = if blocks[i].is_none() { run + 1 } else { 0 };
+                if run == size {
+                    for j in 0..size {
+                        blocks[i + 1 - size + j] = Some(id);
+                        blocks[start + j] = None;
+                    }
+                    break;
+                }
+            }
+        }
+
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| i as u64 * b.unwrap_or(0))
+            .sum()
+    }
+
+    #[test]
+    fn test2_matches_block_simulation() {
+        for len in [1, 2, 19, 500, 2001] {
+            let input = generate(len);
+            assert_eq!(
+                solve2(&parse_input(&input).unwrap()),
+                Ok(move_whole_files(&input)),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test1_matches_block_compaction() {
+        for len in [1, 2, 19, 500, 2001] {
+            let parsed = parse_input(&generate(len)).unwrap();
+            let blocks = compact_blocks(layout(&parsed), |_| {});
+            assert_eq!(solve1(&parsed), layout_checksum(&blocks));
+        }
+
+        // Far too many blocks to lay out one by one
+        let parsed = parse_input("3, 1099511627776, 5, 2, 7").unwrap();
+        let runs = compact_runs(&parsed);
+        assert_eq!(
+            runs,
+            [
+                PlacedFile {
+                    id: 0,
+                    start: 0,
+                    size: 3
+                },
+                PlacedFile {
+                    id: 2,
+                    start: 3,
+                    size: 7
+                },
+                PlacedFile {
+                    id: 1,
+                    start: 10,
+                    size: 5
+                },
+            ]
+        );
+        assert_eq!(
+            solve1(&parsed),
+            Ok(2 * (3 + 4 + 5 + 6 + 7 + 8 + 9) + 10 + 11 + 12 + 13 + 14)
+        );
+    }
+
+    #[test]
+    fn test_checksum_overflow() {
+        // File 1's block positions add up to more than u64::MAX on their own
+        let parsed = parse_input("1 0 8589934592").unwrap();
+        assert_eq!(solve1(&parsed), Err(ChecksumOverflow));
+        assert_eq!(solve2(&parsed), Err(ChecksumOverflow));
+
+        // Just fits, 2^32 blocks of file 1 from 1 sum to 2^31 * (2^32 + 1)
+        let parsed = parse_input("1 0 4294967296").unwrap();
+        assert_eq!(solve1(&parsed), Ok((1 << 31) * ((1 << 32) + 1)));
+
+        assert_eq!(
+            layout_checksum(&vec![None, Some(u64::MAX), Some(1)]),
+            Err(ChecksumOverflow)
+        );
+    }
+
+    #[test]
+    fn test2_large() {
+        let input = generate(1_000_001);
+        let files = compact_files(&parse_input(&input).unwrap(), |_| {});
+        assert_eq!(files.len(), 500_001);
+        assert!(files
+            .windows(2)
+            .all(|w| w[0].start + w[0].size <= w[1].start));
     }
 }